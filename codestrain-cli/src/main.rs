@@ -0,0 +1,76 @@
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use codestrain_engine::*;
+
+const USAGE: &str = "usage: codestrain <strain_a.wasm> <strain_b.wasm> [seed]";
+
+fn load(path: &str, seed: Seed) -> Result<PluginProvider, String> {
+    let wasm = std::fs::read(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    PluginProvider::new(wasm, &ResourceLimits::default(), seed)
+        .map_err(|e| format!("Failed to load plugin {path}: {e}"))
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (path_a, path_b, seed) = match args.as_slice() {
+        [path_a, path_b] => {
+            // a new match every time unless a seed is given
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            (path_a, path_b, nanos as Seed)
+        }
+        [path_a, path_b, seed] => match seed.parse() {
+            Ok(seed) => (path_a, path_b, seed),
+            Err(_) => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let plugins = (
+        load(path_a, strain_seed(seed, Player::A)),
+        load(path_b, strain_seed(seed, Player::B)),
+    );
+    let (plugin_a, plugin_b) = match plugins {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let state = GameState::new(BOARD_SIZE, MOVES_PER_TURN);
+    let result = Match::new(state, plugin_a, plugin_b).run();
+
+    let winner = match result.winner {
+        Some(Player::A) => format!("a ({path_a})"),
+        Some(Player::B) => format!("b ({path_b})"),
+        None => "draw".to_string(),
+    };
+    println!("seed: {seed}");
+    println!("winner: {winner}");
+    println!("score_a: {}", result.score_a);
+    println!("score_b: {}", result.score_b);
+    println!("moves: {}", result.log.len());
+    if let Some(player) = result.forfeit {
+        println!("forfeit: {player:?}");
+    }
+    for violation in &result.violations {
+        println!(
+            "violation: {:?} at move {}: {}",
+            violation.player, violation.turn, violation.reason
+        );
+    }
+    println!();
+    print!("{}", result.state);
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+
+/// The contents of a cell: `None` if it is empty, `Some(true)` if it is friendly and `Some(false)` if it is an enemy.
+pub type Cell = Option<bool>;
+
+/// How a strain wants the board to be sent, returned from its optional `board_encoding` export.
+/// Strains without the export get [`BoardEncoding::Cells`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardEncoding {
+    /// Every cell with its position, in `StrainInput::board`.
+    #[default]
+    Cells,
+    /// A dense [`Board`] in `StrainInput::grid`, leaving `StrainInput::board` empty.
+    Grid,
+}
+
+/// A dense row-major board, with constant time lookups.
+///
+/// Serialized as its size and a string with one character per cell: `.` for empty, `x` for friendly
+/// and `o` for enemy cells.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "EncodedBoard", try_from = "EncodedBoard")]
+pub struct Board {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl Board {
+    /// An empty board.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width * height],
+        }
+    }
+
+    /// A `width` x `height` board with the given cells set, ignoring the ones outside of it.
+    pub fn from_cells(width: usize, height: usize, cells: &[((i32, i32), Cell)]) -> Self {
+        let mut board = Self::new(width, height);
+        for &((x, y), cell) in cells {
+            board.set(x, y, cell);
+        }
+        board
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        self.contains(x, y)
+            .then(|| y as usize * self.width + x as usize)
+    }
+
+    /// The cell at `(x, y)`, or `None` if it is outside of the board.
+    pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
+        self.index(x, y).map(|i| self.cells[i])
+    }
+
+    /// Sets the cell at `(x, y)`, if it is on the board.
+    pub fn set(&mut self, x: i32, y: i32, cell: Cell) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = cell;
+        }
+    }
+
+    /// The positions directly adjacent (without diagonals) to `(x, y)` that are on the board.
+    pub fn neighbors(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+        [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+            .into_iter()
+            .filter(|&(x, y)| self.contains(x, y))
+    }
+
+    /// Every cell with its position, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = ((i32, i32), Cell)> + '_ {
+        self.cells.iter().enumerate().map(|(i, &cell)| {
+            let x = (i % self.width) as i32;
+            let y = (i / self.width) as i32;
+            ((x, y), cell)
+        })
+    }
+
+    /// Number of cells with the given contents.
+    pub fn count(&self, cell: Cell) -> usize {
+        self.cells.iter().filter(|&&c| c == cell).count()
+    }
+
+    /// The board in the [`BoardEncoding::Cells`] format.
+    pub fn to_cells(&self) -> Vec<((i32, i32), Cell)> {
+        self.iter().collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncodedBoard {
+    width: usize,
+    height: usize,
+    cells: String,
+}
+
+impl From<Board> for EncodedBoard {
+    fn from(board: Board) -> Self {
+        let cells = board
+            .cells
+            .iter()
+            .map(|cell| match cell {
+                None => '.',
+                Some(true) => 'x',
+                Some(false) => 'o',
+            })
+            .collect();
+        Self {
+            width: board.width,
+            height: board.height,
+            cells,
+        }
+    }
+}
+
+impl TryFrom<EncodedBoard> for Board {
+    type Error = String;
+
+    fn try_from(encoded: EncodedBoard) -> Result<Self, Self::Error> {
+        let cells = encoded
+            .cells
+            .chars()
+            .map(|c| match c {
+                '.' => Ok(None),
+                'x' => Ok(Some(true)),
+                'o' => Ok(Some(false)),
+                c => Err(format!("invalid cell {c:?}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if cells.len() != encoded.width * encoded.height {
+            return Err(format!(
+                "expected {} cells for a {}x{} board, got {}",
+                encoded.width * encoded.height,
+                encoded.width,
+                encoded.height,
+                cells.len()
+            ));
+        }
+        Ok(Self {
+            width: encoded.width,
+            height: encoded.height,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide_board() -> Board {
+        Board::from_cells(3, 2, &[((0, 0), Some(true)), ((2, 1), Some(false))])
+    }
+
+    #[test]
+    fn encodes_cells_row_by_row() {
+        let json = serde_json::to_string(&wide_board()).unwrap();
+        assert_eq!(json, r#"{"width":3,"height":2,"cells":"x....o"}"#);
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let board = wide_board();
+        let json = serde_json::to_string(&board).unwrap();
+        let decoded: Board = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, board);
+        assert_eq!(decoded.get(2, 1), Some(Some(false)));
+        assert_eq!(decoded.to_cells(), board.to_cells());
+    }
+
+    #[test]
+    fn refuses_invalid_cells() {
+        let error = serde_json::from_str::<Board>(r#"{"width":2,"height":1,"cells":"x?"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("invalid cell '?'"), "{error}");
+    }
+
+    #[test]
+    fn refuses_cells_not_filling_the_board() {
+        let error = serde_json::from_str::<Board>(r#"{"width":3,"height":2,"cells":"x...o"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("expected 6 cells for a 3x2 board, got 5"),
+            "{error}"
+        );
+    }
+}
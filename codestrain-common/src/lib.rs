@@ -1,9 +1,154 @@
 use serde::{Deserialize, Serialize};
 
+mod board;
+
+pub use board::*;
+
+/// Version of [`StrainInput`] sent by this engine.
+///
+/// Inputs from before versioning was introduced only have `board` and `allowed` and are read as version 0,
+/// with every other field left at its default. Version 2 added `grid`, version 3 added `state`.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// The parts of a match that don't change, sent once to the optional `init` export of a strain
+/// before its first move, as seen from its own side of the board.
+///
+/// `init` returns the initial state of the strain for the match as JSON, a string or `null`
+/// (an empty output counts as `null`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchInfo {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    pub moves_per_turn: usize,
+    /// Number of turns after which the match ends, of both players.
+    pub turn_limit: Option<usize>,
+    /// Whether the strain makes the first move of the match.
+    pub first: bool,
+    pub start: (i32, i32),
+    pub opponent_start: (i32, i32),
+}
+
+/// Everything a strain gets to know when it has to make a move, as seen from its own side of the board.
+///
+/// Fields are only ever added, never changed, so strains built against an older version keep working
+/// (unknown fields are ignored when deserializing).
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StrainInput {
+    /// Every cell with its position, empty if the strain asked for [`BoardEncoding::Grid`].
     pub board: Vec<((i32, i32), Option<bool>)>,
     pub allowed: Vec<(i32, i32)>,
+    /// The [`PROTOCOL_VERSION`] of the engine that sent this input.
+    #[serde(default)]
+    pub version: u32,
+    /// Size of the board as seen by the strain.
+    #[serde(default)]
+    pub width: usize,
+    #[serde(default)]
+    pub height: usize,
+    /// Number of completed turns, of both players.
+    #[serde(default)]
+    pub turn: usize,
+    /// Number of moves already made (or skipped) by the strain this turn.
+    #[serde(default)]
+    pub move_in_turn: usize,
+    /// Number of moves the strain has left this turn, including this one.
+    #[serde(default)]
+    pub moves_left: usize,
+    #[serde(default)]
+    pub moves_per_turn: usize,
+    /// The moves the opponent made in its last turn, in order.
+    #[serde(default)]
+    pub opponent_moves: Vec<(i32, i32)>,
+    /// Number of friendly cells on the board.
+    #[serde(default)]
+    pub friendly: i32,
+    /// Number of enemy cells on the board.
+    #[serde(default)]
+    pub enemy: i32,
+    /// The board, if the strain asked for [`BoardEncoding::Grid`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<Board>,
+    /// The state the strain returned from `init` or its last [`StrainResponse::WithState`], if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
+impl StrainInput {
+    /// The board as a [`Board`], whichever way it was sent.
+    pub fn to_grid(&self) -> Board {
+        match &self.grid {
+            Some(grid) => grid.clone(),
+            None => {
+                let (width, height) = self.size();
+                Board::from_cells(width, height, &self.board)
+            }
+        }
+    }
+
+    /// The size of the board, inferred from its cells for inputs that don't have it.
+    pub fn size(&self) -> (usize, usize) {
+        if let Some(grid) = &self.grid {
+            return (grid.width(), grid.height());
+        }
+        if self.version >= 1 {
+            return (self.width, self.height);
+        }
+        let max = |f: fn(&(i32, i32)) -> i32| {
+            self.board
+                .iter()
+                .map(|(pos, _)| f(pos) + 1)
+                .max()
+                .unwrap_or(0) as usize
+        };
+        (max(|pos| pos.0), max(|pos| pos.1))
+    }
 }
 
 pub type StrainOutput = (i32, i32);
+
+/// What `take_turn` can return: just a move, which keeps the state of the strain as it is,
+/// or a move along with a new state (`null` clears it).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StrainResponse {
+    Move(StrainOutput),
+    WithState {
+        r#move: StrainOutput,
+        state: Option<String>,
+    },
+}
+
+impl StrainResponse {
+    pub fn position(&self) -> StrainOutput {
+        match self {
+            Self::Move(position)
+            | Self::WithState {
+                r#move: position, ..
+            } => *position,
+        }
+    }
+}
+
+/// What the optional `take_moves` export returns: the moves to make in order, at most
+/// `StrainInput::moves_left` of them, with or without a new state like [`StrainResponse`].
+///
+/// Strains exporting `take_moves` are only asked for moves through it. If the moves run out before the
+/// turn ends, `take_moves` is called again for the rest of the turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TurnResponse {
+    Moves(Vec<StrainOutput>),
+    WithState {
+        moves: Vec<StrainOutput>,
+        state: Option<String>,
+    },
+}
+
+impl TurnResponse {
+    pub fn moves(&self) -> &[StrainOutput] {
+        match self {
+            Self::Moves(moves) | Self::WithState { moves, .. } => moves,
+        }
+    }
+}
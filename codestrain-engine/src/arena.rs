@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ArenaError, Player, Position, BOARD_SIZE, MOVES_PER_TURN};
+
+/// The board and rules a match is played with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct Arena {
+    pub width: usize,
+    pub height: usize,
+    pub moves_per_turn: usize,
+    /// Number of turns after which the game ends, `None` to play until neither player can move.
+    pub turn_limit: Option<usize>,
+    /// Starting cell of player a on the real board.
+    pub start_a: Position,
+    /// Starting cell of player b on the real board.
+    pub start_b: Position,
+}
+
+impl Arena {
+    /// A square board with the players in opposite corners.
+    pub fn square(size: usize, moves_per_turn: usize) -> Self {
+        let max = size as i32 - 1;
+        Self {
+            width: size,
+            height: size,
+            moves_per_turn,
+            turn_limit: None,
+            start_a: (0, 0),
+            start_b: (max, max),
+        }
+    }
+
+    pub fn start(&self, player: Player) -> Position {
+        match player {
+            Player::A => self.start_a,
+            Player::B => self.start_b,
+        }
+    }
+
+    pub fn contains(&self, (x, y): Position) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    pub fn validate(&self) -> Result<(), ArenaError> {
+        // positions are i32, so every cell must be addressable with one
+        if i32::try_from(self.width.max(self.height)).is_err() {
+            return Err(ArenaError::TooLarge);
+        }
+        let cells = self
+            .width
+            .checked_mul(self.height)
+            .ok_or(ArenaError::TooLarge)?;
+        if cells < 2 {
+            return Err(ArenaError::TooSmall);
+        }
+        if self.moves_per_turn == 0 {
+            return Err(ArenaError::NoMoves);
+        }
+        if self.turn_limit == Some(0) {
+            return Err(ArenaError::NoTurns);
+        }
+        for start in [self.start_a, self.start_b] {
+            if !self.contains(start) {
+                return Err(ArenaError::StartOutOfBounds(start));
+            }
+        }
+        if self.start_a == self.start_b {
+            return Err(ArenaError::SameStart(self.start_a));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Arena {
+    /// A small board, wider than it is tall, so mixing up its sides shows in tests.
+    pub(crate) fn wide() -> Self {
+        Self {
+            width: 5,
+            height: 3,
+            moves_per_turn: 2,
+            turn_limit: None,
+            start_a: (0, 0),
+            start_b: (4, 2),
+        }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::square(BOARD_SIZE, MOVES_PER_TURN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_arena_is_valid() {
+        assert_eq!(Arena::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn huge_arenas_are_refused_without_overflowing() {
+        let arena = Arena {
+            width: usize::MAX,
+            height: usize::MAX,
+            ..Arena::default()
+        };
+        assert_eq!(arena.validate(), Err(ArenaError::TooLarge));
+    }
+
+    #[test]
+    fn starts_must_be_distinct_cells_on_the_board() {
+        let arena = Arena {
+            start_b: (24, 0),
+            ..Arena::default()
+        };
+        assert_eq!(arena.validate(), Err(ArenaError::StartOutOfBounds((24, 0))));
+        let arena = Arena {
+            start_b: (0, 0),
+            ..Arena::default()
+        };
+        assert_eq!(arena.validate(), Err(ArenaError::SameStart((0, 0))));
+    }
+}
@@ -0,0 +1,86 @@
+use thiserror::Error;
+
+use crate::Position;
+
+/// Ways a strain can fail to produce a move.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StrainError {
+    #[error("Failed to load plugin: {0}")]
+    LoadFailed(String),
+    #[error("Plugin trapped: {0}")]
+    Trap(String),
+    #[error("Plugin returned invalid JSON: {0}")]
+    InvalidJson(String),
+    #[error("Illegal move {0:?}")]
+    IllegalMove(Position),
+    #[error("Strain returned no moves")]
+    NoMoves,
+    #[error("Strain returned {0} moves but had fewer left in its turn")]
+    TooManyMoves(usize),
+    #[error("Move used more fuel than a single call may")]
+    OutOfFuel,
+    #[error("Strain used up its fuel for the match")]
+    MatchFuel,
+    #[error("Move took longer than the call timeout")]
+    Timeout,
+    #[error("Strain used up its time budget for the match")]
+    MatchBudget,
+    #[error("Strain ran out of memory")]
+    Memory,
+}
+
+impl StrainError {
+    /// Whether the strain loses the whole match instead of just the move.
+    pub fn forfeits_match(&self) -> bool {
+        matches!(
+            self,
+            Self::LoadFailed(_) | Self::Trap(_) | Self::MatchFuel | Self::MatchBudget
+        )
+    }
+
+    /// Stable identifier used when storing the reason.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LoadFailed(_) => "load_failed",
+            Self::Trap(_) => "trap",
+            Self::InvalidJson(_) => "invalid_json",
+            Self::IllegalMove(_) => "illegal_move",
+            Self::NoMoves => "no_moves",
+            Self::TooManyMoves(_) => "too_many_moves",
+            Self::OutOfFuel => "out_of_fuel",
+            Self::MatchFuel => "match_fuel",
+            Self::Timeout => "timeout",
+            Self::MatchBudget => "match_budget",
+            Self::Memory => "memory",
+        }
+    }
+
+    /// Extra information stored along with the reason, if there is any.
+    pub fn details(&self) -> Option<String> {
+        match self {
+            Self::LoadFailed(message) | Self::Trap(message) | Self::InvalidJson(message) => {
+                Some(message.clone())
+            }
+            Self::IllegalMove((x, y)) => Some(format!("({x}, {y})")),
+            Self::TooManyMoves(count) => Some(count.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Ways an arena can be impossible to play on.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ArenaError {
+    #[error("The board must have room for both players")]
+    TooSmall,
+    #[error("The board is too large")]
+    TooLarge,
+    #[error("Players must have at least one move per turn")]
+    NoMoves,
+    #[error("The turn limit must allow at least one turn")]
+    NoTurns,
+    #[error("Starting position {0:?} is outside the board")]
+    StartOutOfBounds(Position),
+    #[error("Both players start at {0:?}")]
+    SameStart(Position),
+}
@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use codestrain_common::{BoardEncoding, StrainInput, PROTOCOL_VERSION};
+
+use crate::{Board, GameState, MoveProvider, Player, Position, StrainError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveLog {
+    pub player: Player,
+    pub x: i32,
+    pub y: i32,
+    /// Wall-clock time the provider took to pick the move.
+    /// Moves returned together are all timed on the first one, the rest take no time.
+    pub duration: Duration,
+    /// Fuel the provider used to pick the move, if it runs on fuel. Counted like `duration`.
+    pub fuel: Option<u64>,
+    /// Number of moves the player could choose from.
+    pub allowed: usize,
+}
+
+/// A strain failing to make a valid move, counted against it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub player: Player,
+    /// Number of moves in the log when the violation happened.
+    pub turn: usize,
+    pub reason: StrainError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchResult {
+    pub state: GameState,
+    pub winner: Option<Player>,
+    pub score_a: i32,
+    pub score_b: i32,
+    pub log: Vec<MoveLog>,
+    pub violations: Vec<Violation>,
+    /// The player that forfeited the match, if any.
+    pub forfeit: Option<Player>,
+}
+
+/// A single game between two move providers.
+pub struct Match<A, B> {
+    state: GameState,
+    player_a: A,
+    player_b: B,
+    log: Vec<MoveLog>,
+    violations: Vec<Violation>,
+    forfeit: Option<Player>,
+    started: bool,
+    /// Moves of the active player that were returned together and are still to be made, on its view.
+    queued: VecDeque<Position>,
+}
+
+impl<A: MoveProvider, B: MoveProvider> Match<A, B> {
+    pub fn new(state: GameState, player_a: A, player_b: B) -> Self {
+        Self {
+            state,
+            player_a,
+            player_b,
+            log: vec![],
+            violations: vec![],
+            forfeit: None,
+            started: false,
+            queued: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn log(&self) -> &[MoveLog] {
+        &self.log
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Plays a single move of the active player.
+    /// Returns the move if one was made, or `None` if the player had to skip.
+    pub fn step(&mut self) -> Option<MoveLog> {
+        if !self.started {
+            self.start();
+        }
+        if self.state.is_finished() {
+            return None;
+        }
+        let player = self.state.active_player();
+        // the starting cell should always be friendly
+        let start = self.state.arena().start(player);
+        if !self
+            .state
+            .board()
+            .iter()
+            .any(|(pos, v)| *pos == start && *v == Some(player.as_bool()))
+        {
+            // something extremely weird happened, end the game
+            self.state.finish();
+            return None;
+        }
+
+        let view = self.state.view(player);
+        match self.request_move(player, view) {
+            Ok(Some(entry)) => {
+                self.state.apply_move((entry.x, entry.y));
+                self.log.push(entry);
+                Some(entry)
+            }
+            Ok(None) => {
+                self.state.skip();
+                None
+            }
+            Err(reason) => {
+                if reason.forfeits_match() {
+                    self.forfeit = Some(player);
+                    self.state.finish();
+                } else {
+                    self.state.skip();
+                }
+                self.violations.push(Violation {
+                    player,
+                    turn: self.log.len(),
+                    reason,
+                });
+                None
+            }
+        }
+    }
+
+    /// Lets both providers prepare for the match, before the first move.
+    fn start(&mut self) {
+        self.started = true;
+        for player in [Player::A, Player::B] {
+            let info = self.state.match_info(player);
+            let result = match player {
+                Player::A => self.player_a.init(info),
+                Player::B => self.player_b.init(info),
+            };
+            if let Err(reason) = result {
+                let forfeits = reason.forfeits_match();
+                self.violations.push(Violation {
+                    player,
+                    turn: 0,
+                    reason,
+                });
+                if forfeits {
+                    self.forfeit = Some(player);
+                    self.state.finish();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Asks the provider of `player` for a move and checks it against the rules.
+    /// Returns the move on the real board, or `None` if there was no move to make.
+    fn request_move(
+        &mut self,
+        player: Player,
+        view: Board,
+    ) -> Result<Option<MoveLog>, StrainError> {
+        let allowed = GameState::allowed_moves(&view);
+        if allowed.is_empty() {
+            self.queued.clear();
+            return Ok(None);
+        }
+        let started = Instant::now();
+        // moves returned together with an earlier one took no fuel of their own
+        let mut fuel = match player {
+            Player::A => self.player_a.fuel_spent(),
+            Player::B => self.player_b.fuel_spent(),
+        }
+        .map(|_| 0);
+        if self.queued.is_empty() {
+            let moves_left = self.state.moves_per_turn() - self.state.move_in_turn();
+            let input = self.input(player, view, allowed.clone());
+            let provider: &mut dyn MoveProvider = match player {
+                Player::A => &mut self.player_a,
+                Player::B => &mut self.player_b,
+            };
+            let fuel_before = provider.fuel_spent();
+            if provider.takes_moves() {
+                let moves = provider.take_moves(input)?;
+                if moves.is_empty() {
+                    return Err(StrainError::NoMoves);
+                }
+                if moves.len() > moves_left {
+                    return Err(StrainError::TooManyMoves(moves.len()));
+                }
+                self.queued.extend(moves);
+            } else {
+                self.queued.push_back(provider.take_turn(input)?);
+            }
+            fuel = provider
+                .fuel_spent()
+                .zip(fuel_before)
+                .map(|(after, before)| after - before);
+        }
+        let duration = started.elapsed();
+        let response = self.queued.pop_front().expect("No queued move");
+        if !allowed.contains(&response) {
+            // the rest of the moves were planned on top of this one
+            self.queued.clear();
+            return Err(StrainError::IllegalMove(response));
+        }
+        let (x, y) = self.state.to_board(player, response);
+        Ok(Some(MoveLog {
+            player,
+            x,
+            y,
+            duration,
+            fuel,
+            allowed: allowed.len(),
+        }))
+    }
+
+    /// What `player` is told about the game when it has to move.
+    fn input(&self, player: Player, view: Board, allowed: Vec<Position>) -> StrainInput {
+        let (width, height) = self.state.view_size(player);
+        let move_in_turn = self.state.move_in_turn();
+        let encoding = match player {
+            Player::A => self.player_a.board_encoding(),
+            Player::B => self.player_b.board_encoding(),
+        };
+        let (board, grid) = match encoding {
+            BoardEncoding::Cells => (view, None),
+            BoardEncoding::Grid => (
+                vec![],
+                Some(codestrain_common::Board::from_cells(width, height, &view)),
+            ),
+        };
+        StrainInput {
+            board,
+            allowed,
+            version: PROTOCOL_VERSION,
+            width,
+            height,
+            turn: self.state.turns(),
+            move_in_turn,
+            moves_left: self.state.moves_per_turn() - move_in_turn,
+            moves_per_turn: self.state.moves_per_turn(),
+            opponent_moves: self
+                .state
+                .last_moves(player.opponent())
+                .iter()
+                .map(|&pos| self.state.to_view(player, pos))
+                .collect(),
+            friendly: self.state.score(player),
+            enemy: self.state.score(player.opponent()),
+            grid,
+            state: None,
+        }
+    }
+
+    /// Plays until neither player can move anymore, or one of them forfeits.
+    pub fn run(self) -> MatchResult {
+        self.run_with(|_, _| {})
+    }
+
+    /// Like [`Match::run`], but calls `on_move` with the state after every move that is made.
+    pub fn run_with(mut self, mut on_move: impl FnMut(&GameState, MoveLog)) -> MatchResult {
+        while !self.state.is_finished() {
+            if let Some(entry) = self.step() {
+                on_move(&self.state, entry);
+            }
+        }
+        MatchResult {
+            winner: match self.forfeit {
+                Some(player) => Some(player.opponent()),
+                None => self.state.winner(),
+            },
+            score_a: self.state.score(Player::A),
+            score_b: self.state.score(Player::B),
+            state: self.state,
+            log: self.log,
+            violations: self.violations,
+            forfeit: self.forfeit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use codestrain_common::{StrainInput, StrainOutput};
+
+    use super::*;
+    use crate::Arena;
+
+    /// Returns the given batches of moves, one per call, and then nothing.
+    struct Batches(VecDeque<Vec<StrainOutput>>);
+
+    impl Batches {
+        fn new(batches: impl IntoIterator<Item = Vec<StrainOutput>>) -> Self {
+            Self(batches.into_iter().collect())
+        }
+    }
+
+    impl MoveProvider for Batches {
+        fn take_turn(&mut self, _input: StrainInput) -> Result<StrainOutput, StrainError> {
+            unreachable!("Batches only take moves together")
+        }
+
+        fn takes_moves(&self) -> bool {
+            true
+        }
+
+        fn take_moves(&mut self, _input: StrainInput) -> Result<Vec<StrainOutput>, StrainError> {
+            Ok(self.0.pop_front().unwrap_or_default())
+        }
+    }
+
+    fn wide_state(moves_per_turn: usize) -> GameState {
+        GameState::with_arena(Arena {
+            moves_per_turn,
+            ..Arena::wide()
+        })
+        .unwrap()
+    }
+
+    fn moves(game: &Match<Batches, Batches>) -> Vec<(Player, Position)> {
+        game.log()
+            .iter()
+            .map(|entry| (entry.player, (entry.x, entry.y)))
+            .collect()
+    }
+
+    fn reasons(game: &Match<Batches, Batches>) -> Vec<StrainError> {
+        game.violations()
+            .iter()
+            .map(|violation| violation.reason.clone())
+            .collect()
+    }
+
+    #[test]
+    fn moves_are_checked_after_the_ones_before_them() {
+        // (2, 0) is only next to a friendly cell once (1, 0) is taken
+        let a = Batches::new([vec![(1, 0), (2, 0)]]);
+        let mut game = Match::new(wide_state(2), a, Batches::new([]));
+        game.step();
+        game.step();
+        assert_eq!(moves(&game), vec![(Player::A, (1, 0)), (Player::A, (2, 0))]);
+        assert!(game.violations().is_empty());
+    }
+
+    #[test]
+    fn an_empty_batch_is_a_violation() {
+        let mut game = Match::new(wide_state(2), Batches::new([]), Batches::new([]));
+        assert_eq!(game.step(), None);
+        assert_eq!(reasons(&game), vec![StrainError::NoMoves]);
+        assert_eq!(game.state().move_in_turn(), 1);
+    }
+
+    #[test]
+    fn too_many_moves_are_refused_before_any_is_made() {
+        let a = Batches::new([vec![(1, 0), (2, 0), (3, 0)]]);
+        let mut game = Match::new(wide_state(2), a, Batches::new([]));
+        assert_eq!(game.step(), None);
+        assert!(game.log().is_empty());
+        assert_eq!(reasons(&game), vec![StrainError::TooManyMoves(3)]);
+    }
+
+    #[test]
+    fn an_illegal_move_drops_the_rest_of_the_batch() {
+        let a = Batches::new([vec![(1, 0), (3, 0), (2, 0)], vec![(2, 0)]]);
+        let mut game = Match::new(wide_state(3), a, Batches::new([]));
+        game.step();
+        assert_eq!(game.step(), None);
+        assert_eq!(reasons(&game), vec![StrainError::IllegalMove((3, 0))]);
+        // the provider is asked again instead of playing (2, 0) from the dropped batch
+        game.step();
+        assert_eq!(moves(&game), vec![(Player::A, (1, 0)), (Player::A, (2, 0))]);
+        assert_eq!(game.state().active_player(), Player::B);
+    }
+
+    #[test]
+    fn moves_of_player_b_are_mapped_back_to_the_board() {
+        let a = Batches::new([vec![(1, 0)]]);
+        let b = Batches::new([vec![(1, 0)], vec![(0, 1)]]);
+        let mut game = Match::new(wide_state(1), a, b);
+        game.step();
+        game.step();
+        game.step();
+        game.step();
+        assert_eq!(
+            moves(&game),
+            vec![
+                (Player::A, (1, 0)),
+                (Player::B, (4, 1)),
+                (Player::B, (3, 2)),
+            ]
+        );
+        assert!(game.violations().iter().all(|v| v.player == Player::A));
+    }
+}
@@ -0,0 +1,17 @@
+mod arena;
+mod error;
+mod game;
+mod limits;
+mod provider;
+mod seed;
+mod state;
+mod validate;
+
+pub use arena::*;
+pub use error::*;
+pub use game::*;
+pub use limits::*;
+pub use provider::*;
+pub use seed::*;
+pub use state::*;
+pub use validate::*;
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+/// Resource limits applied to every strain plugin.
+///
+/// Strains are limited by fuel, roughly the number of wasm instructions they run, so a match plays
+/// out the same way however busy the host is. The wall-clock limits are only a backstop for time
+/// that fuel doesn't count, and are set well above what the fuel limits allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Maximum fuel of a single call into the strain.
+    pub call_fuel: u64,
+    /// Maximum total fuel a strain may use over a whole match.
+    pub match_fuel: u64,
+    /// Maximum wall-clock time of a single call.
+    pub call_timeout: Duration,
+    /// Maximum total wall-clock time a strain may spend in its calls over a whole match.
+    pub match_budget: Duration,
+    /// Maximum size of the plugin memory, in 64 KiB wasm pages.
+    pub memory_max_pages: u32,
+    /// Maximum size of the wasm module, checked when a strain is uploaded.
+    pub max_wasm_bytes: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            call_fuel: 100_000_000,
+            match_fuel: 2_000_000_000,
+            call_timeout: Duration::from_secs(1),
+            match_budget: Duration::from_secs(60),
+            // 64 MiB
+            memory_max_pages: 1024,
+            max_wasm_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::time::{Duration, Instant};
+
+use codestrain_common::{
+    BoardEncoding, MatchInfo, StrainInput, StrainOutput, StrainResponse, TurnResponse,
+};
+use extism::{
+    convert::{Json, ToBytes},
+    CurrentPlugin, Function, Manifest, Plugin, PluginBuilder, UserData, Val, ValType, Wasm,
+};
+
+use crate::{ResourceLimits, Seed, SeededRng, StrainError};
+
+/// Something that can pick moves for one side of a match.
+pub trait MoveProvider {
+    fn take_turn(&mut self, input: StrainInput) -> Result<StrainOutput, StrainError>;
+
+    /// How the board should be sent in [`StrainInput`].
+    fn board_encoding(&self) -> BoardEncoding {
+        BoardEncoding::Cells
+    }
+
+    /// Called once before the first move of a match.
+    fn init(&mut self, _info: MatchInfo) -> Result<(), StrainError> {
+        Ok(())
+    }
+
+    /// Whether moves should be asked for through [`MoveProvider::take_moves`] instead.
+    fn takes_moves(&self) -> bool {
+        false
+    }
+
+    /// Picks the next moves of the turn, in order, at most `input.moves_left` of them.
+    fn take_moves(&mut self, input: StrainInput) -> Result<Vec<StrainOutput>, StrainError> {
+        Ok(vec![self.take_turn(input)?])
+    }
+
+    /// Total fuel used over the match so far, for providers that run on fuel.
+    fn fuel_spent(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl<F: FnMut(StrainInput) -> StrainOutput> MoveProvider for F {
+    fn take_turn(&mut self, input: StrainInput) -> Result<StrainOutput, StrainError> {
+        Ok(self(input))
+    }
+}
+
+impl MoveProvider for Box<dyn MoveProvider> {
+    fn take_turn(&mut self, input: StrainInput) -> Result<StrainOutput, StrainError> {
+        (**self).take_turn(input)
+    }
+
+    fn board_encoding(&self) -> BoardEncoding {
+        (**self).board_encoding()
+    }
+
+    fn init(&mut self, info: MatchInfo) -> Result<(), StrainError> {
+        (**self).init(info)
+    }
+
+    fn takes_moves(&self) -> bool {
+        (**self).takes_moves()
+    }
+
+    fn take_moves(&mut self, input: StrainInput) -> Result<Vec<StrainOutput>, StrainError> {
+        (**self).take_moves(input)
+    }
+
+    fn fuel_spent(&self) -> Option<u64> {
+        (**self).fuel_spent()
+    }
+}
+
+/// A provider that fails every move with the same error, e.g. for a strain that could not be loaded.
+pub struct BrokenProvider(pub StrainError);
+
+impl MoveProvider for BrokenProvider {
+    fn take_turn(&mut self, _input: StrainInput) -> Result<StrainOutput, StrainError> {
+        Err(self.0.clone())
+    }
+}
+
+/// Logical milliseconds that pass for a strain on every call into it.
+const CALL_MILLIS: u64 = 1000;
+
+/// What the host functions of a strain see, driven by its seed instead of the real world.
+struct HostState {
+    rng: SeededRng,
+    /// Logical milliseconds since the match started.
+    clock: u64,
+}
+
+/// `codestrain_random() -> i64`, the next number of the strain's seeded generator.
+fn host_random(
+    _plugin: &mut CurrentPlugin,
+    _inputs: &[Val],
+    outputs: &mut [Val],
+    host: UserData<HostState>,
+) -> Result<(), extism::Error> {
+    let host = host.get()?;
+    outputs[0] = Val::I64(host.lock().unwrap().rng.next_u64() as i64);
+    Ok(())
+}
+
+/// `codestrain_time() -> i64`, logical milliseconds since the match started.
+/// Reading it moves the clock forward too, so waiting for it in a loop ends.
+fn host_time(
+    _plugin: &mut CurrentPlugin,
+    _inputs: &[Val],
+    outputs: &mut [Val],
+    host: UserData<HostState>,
+) -> Result<(), extism::Error> {
+    let host = host.get()?;
+    let mut host = host.lock().unwrap();
+    host.clock += 1;
+    outputs[0] = Val::I64(host.clock as i64);
+    Ok(())
+}
+
+/// A strain compiled to wasm, called through its `take_turn` export.
+/// It can also export `board_encoding` to choose how it gets the board, `init` to set up its
+/// state for the match, and `take_moves` to make all moves of a turn at once.
+///
+/// Strains run without WASI, so the only randomness and time they can get are the `codestrain_random`
+/// and `codestrain_time` host functions, both derived from the seed they are loaded with.
+pub struct PluginProvider {
+    plugin: Plugin,
+    host: UserData<HostState>,
+    encoding: BoardEncoding,
+    batch: bool,
+    /// Handed back to the strain on every move.
+    state: Option<String>,
+    fuel_budget: u64,
+    fuel_spent: u64,
+    budget: Duration,
+    spent: Duration,
+}
+
+impl PluginProvider {
+    pub fn new(
+        wasm: impl Into<Vec<u8>>,
+        limits: &ResourceLimits,
+        seed: Seed,
+    ) -> Result<Self, extism::Error> {
+        let manifest = Manifest::new([Wasm::data(wasm.into())])
+            .with_timeout(limits.call_timeout)
+            .with_memory_max(limits.memory_max_pages);
+        let host = UserData::new(HostState {
+            rng: SeededRng::new(seed),
+            clock: 0,
+        });
+        let functions = [
+            Function::new(
+                "codestrain_random",
+                [],
+                [ValType::I64],
+                host.clone(),
+                host_random,
+            ),
+            Function::new(
+                "codestrain_time",
+                [],
+                [ValType::I64],
+                host.clone(),
+                host_time,
+            ),
+        ];
+        let mut plugin = PluginBuilder::new(manifest)
+            .with_functions(functions)
+            .with_wasi(false)
+            .with_fuel_limit(limits.call_fuel)
+            .build()?;
+        let encoding = if plugin.function_exists("board_encoding") {
+            plugin
+                .call::<(), Json<BoardEncoding>>("board_encoding", ())?
+                .0
+        } else {
+            BoardEncoding::Cells
+        };
+        let batch = plugin.function_exists("take_moves");
+        Ok(Self {
+            plugin,
+            host,
+            encoding,
+            batch,
+            state: None,
+            fuel_budget: limits.match_fuel,
+            fuel_spent: 0,
+            budget: limits.match_budget,
+            spent: Duration::ZERO,
+        })
+    }
+
+    /// Loads the plugin, or a provider that forfeits on its first move if that fails.
+    pub fn load(
+        wasm: impl Into<Vec<u8>>,
+        limits: &ResourceLimits,
+        seed: Seed,
+    ) -> Box<dyn MoveProvider> {
+        match Self::new(wasm, limits, seed) {
+            Ok(provider) => Box::new(provider),
+            Err(e) => Box::new(BrokenProvider(StrainError::LoadFailed(e.to_string()))),
+        }
+    }
+
+    /// Total time spent in `init` and `take_turn` so far.
+    pub fn spent(&self) -> Duration {
+        self.spent
+    }
+
+    /// Calls an export of the plugin, counting the fuel and time against the budgets of the match.
+    fn call<'a>(&mut self, name: &str, input: impl ToBytes<'a>) -> Result<Vec<u8>, StrainError> {
+        if self.fuel_spent >= self.fuel_budget {
+            return Err(StrainError::MatchFuel);
+        }
+        if self.spent >= self.budget {
+            return Err(StrainError::MatchBudget);
+        }
+        if let Ok(host) = self.host.get() {
+            host.lock().unwrap().clock += CALL_MILLIS;
+        }
+        let start = Instant::now();
+        let result = self
+            .plugin
+            .call::<_, &[u8]>(name, input)
+            .map(|output| output.to_vec())
+            .map_err(|e| match e.root_cause().to_string().as_str() {
+                // extism reports these with fixed messages
+                "plugin ran out of fuel" => StrainError::OutOfFuel,
+                "timeout" => StrainError::Timeout,
+                "oom" => StrainError::Memory,
+                _ => StrainError::Trap(e.to_string()),
+            });
+        self.spent += start.elapsed();
+        self.fuel_spent += self.plugin.fuel_consumed().unwrap_or_default();
+        if self.fuel_spent > self.fuel_budget {
+            return Err(StrainError::MatchFuel);
+        }
+        if self.spent > self.budget {
+            return Err(StrainError::MatchBudget);
+        }
+        result
+    }
+}
+
+impl MoveProvider for PluginProvider {
+    fn take_turn(&mut self, mut input: StrainInput) -> Result<StrainOutput, StrainError> {
+        input.state = self.state.clone();
+        let output = self.call("take_turn", Json(input))?;
+        let response = serde_json::from_slice::<StrainResponse>(&output)
+            .map_err(|e| StrainError::InvalidJson(e.to_string()))?;
+        if let StrainResponse::WithState { state, .. } = &response {
+            self.state = state.clone();
+        }
+        Ok(response.position())
+    }
+
+    fn takes_moves(&self) -> bool {
+        self.batch
+    }
+
+    fn take_moves(&mut self, mut input: StrainInput) -> Result<Vec<StrainOutput>, StrainError> {
+        input.state = self.state.clone();
+        let output = self.call("take_moves", Json(input))?;
+        let response = serde_json::from_slice::<TurnResponse>(&output)
+            .map_err(|e| StrainError::InvalidJson(e.to_string()))?;
+        let moves = response.moves().to_vec();
+        if let TurnResponse::WithState { state, .. } = response {
+            self.state = state;
+        }
+        Ok(moves)
+    }
+
+    fn init(&mut self, info: MatchInfo) -> Result<(), StrainError> {
+        self.state = None;
+        if !self.plugin.function_exists("init") {
+            return Ok(());
+        }
+        let output = self.call("init", Json(info))?;
+        if !output.is_empty() {
+            self.state = serde_json::from_slice::<Option<String>>(&output)
+                .map_err(|e| StrainError::InvalidJson(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn board_encoding(&self) -> BoardEncoding {
+        self.encoding
+    }
+
+    fn fuel_spent(&self) -> Option<u64> {
+        Some(self.fuel_spent)
+    }
+}
@@ -0,0 +1,56 @@
+use crate::Player;
+
+/// The seed of a match. Everything random or time dependent a strain can observe is derived from it,
+/// so a match between the same strains with the same seed plays out the same way.
+pub type Seed = u64;
+
+/// The seed of the strain playing as `player`, so both strains don't see the same numbers.
+pub fn strain_seed(seed: Seed, player: Player) -> Seed {
+    SeededRng::new(seed ^ player.as_bool() as u64).next_u64()
+}
+
+/// A small deterministic random number generator (SplitMix64), not suitable for cryptography.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng(u64);
+
+impl SeededRng {
+    pub fn new(seed: Seed) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_the_same_numbers() {
+        let mut a = SeededRng::new(42);
+        let mut b = SeededRng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(SeededRng::new(42).next_u64(), SeededRng::new(43).next_u64());
+    }
+
+    #[test]
+    fn matches_the_reference_splitmix64() {
+        let mut rng = SeededRng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220a8397b1dcdaf);
+        assert_eq!(rng.next_u64(), 0x6e789e6aa1b965f4);
+    }
+
+    #[test]
+    fn players_get_their_own_seeds() {
+        assert_ne!(strain_seed(7, Player::A), strain_seed(7, Player::B));
+        assert_eq!(strain_seed(7, Player::B), strain_seed(7, Player::B));
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+
+use codestrain_common::{MatchInfo, StrainOutput, PROTOCOL_VERSION};
+
+use crate::{Arena, ArenaError};
+
+pub const BOARD_SIZE: usize = 24;
+pub const MOVES_PER_TURN: usize = 5;
+
+/// A cell position on the board, `(x, y)`.
+pub type Position = (i32, i32);
+/// The board as sent to strains.
+///
+/// None = empty
+/// Some(true) = friendly (player a on the real board)
+/// Some(false) = enemy (player b on the real board)
+pub type Board = Vec<(Position, Option<bool>)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    A,
+    B,
+}
+
+impl Player {
+    pub fn opponent(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+        }
+    }
+
+    /// The value this player's cells have on the real board.
+    pub fn as_bool(self) -> bool {
+        self == Self::A
+    }
+}
+
+impl From<bool> for Player {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::A
+        } else {
+            Self::B
+        }
+    }
+}
+
+/// The rules of the game, independent of who is playing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState {
+    arena: Arena,
+    board: Board,
+    turn: Player,
+    move_in_turn: usize,
+    /// Number of completed turns.
+    turns: usize,
+    skips: usize,
+    finished: bool,
+    /// Cells claimed by each player in their current or most recent turn, a's first.
+    last_moves: [Vec<Position>; 2],
+}
+
+impl GameState {
+    /// A square board with the players in opposite corners.
+    ///
+    /// Panics if the board has room for less than two cells or there are no moves per turn.
+    pub fn new(board_size: usize, moves_per_turn: usize) -> Self {
+        Self::with_arena(Arena::square(board_size, moves_per_turn)).expect("Invalid arena")
+    }
+
+    pub fn with_arena(arena: Arena) -> Result<Self, ArenaError> {
+        arena.validate()?;
+        let mut board = vec![];
+        for y in 0..arena.height as i32 {
+            for x in 0..arena.width as i32 {
+                let value = if (x, y) == arena.start_a {
+                    Some(true)
+                } else if (x, y) == arena.start_b {
+                    Some(false)
+                } else {
+                    None
+                };
+                board.push(((x, y), value));
+            }
+        }
+        Ok(Self {
+            arena,
+            board,
+            turn: Player::A,
+            move_in_turn: 0,
+            turns: 0,
+            skips: 0,
+            finished: false,
+            last_moves: [vec![], vec![]],
+        })
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    pub fn moves_per_turn(&self) -> usize {
+        self.arena.moves_per_turn
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn active_player(&self) -> Player {
+        self.turn
+    }
+
+    /// Number of completed turns, of both players.
+    pub fn turns(&self) -> usize {
+        self.turns
+    }
+
+    /// Number of moves the active player has already made or skipped this turn.
+    pub fn move_in_turn(&self) -> usize {
+        self.move_in_turn
+    }
+
+    /// Cells (on the real board) claimed by `player` in their current turn, or in their last one
+    /// if it is not their turn.
+    pub fn last_moves(&self, player: Player) -> &[Position] {
+        &self.last_moves[player as usize]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Maps a position on the real board to the view of player b.
+    /// The board is mirrored along its anti-diagonal, so a `width` x `height` board is seen as
+    /// `height` x `width`.
+    pub fn rotate(&self, (x, y): Position) -> Position {
+        let max_x = self.arena.width as i32 - 1;
+        let max_y = self.arena.height as i32 - 1;
+        (max_y - y, max_x - x)
+    }
+
+    /// Maps a position on the view of player b back to the real board, the inverse of [`GameState::rotate`].
+    pub fn unrotate(&self, (x, y): Position) -> Position {
+        let max_x = self.arena.width as i32 - 1;
+        let max_y = self.arena.height as i32 - 1;
+        (max_x - y, max_y - x)
+    }
+
+    /// The board as seen by `player`.
+    ///
+    /// The board is rotated so that both players see it the same way (on the default arena they
+    /// are always at the top left), this way the plugin doesn't have to worry about the board rotation.
+    /// The mappings are also changed so that friendly cells are always true and enemy cells are always false.
+    pub fn view(&self, player: Player) -> Board {
+        match player {
+            Player::A => self.board.clone(),
+            Player::B => self
+                .board
+                .iter()
+                .map(|(pos, v)| (self.rotate(*pos), v.map(|v| !v)))
+                .collect(),
+        }
+    }
+
+    /// Empty cells directly adjacent (without diagonals) to a friendly cell of `view`.
+    pub fn allowed_moves(view: &Board) -> Vec<Position> {
+        let friendly = view
+            .iter()
+            .filter(|(_, v)| *v == Some(true))
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        view.iter()
+            .filter(|(_, v)| v.is_none())
+            .map(|(k, _)| *k)
+            .filter(|(x, y)| {
+                friendly.iter().any(|(ox, oy)| {
+                    (x == ox && (y - oy).abs() == 1) || (y == oy && (x - ox).abs() == 1)
+                })
+            })
+            .collect()
+    }
+
+    /// The width and height of the board as seen by `player`.
+    pub fn view_size(&self, player: Player) -> (usize, usize) {
+        // the board of player b is mirrored, so its sides are swapped
+        match player {
+            Player::A => (self.arena.width, self.arena.height),
+            Player::B => (self.arena.height, self.arena.width),
+        }
+    }
+
+    /// What `player` is told about the match before it starts.
+    pub fn match_info(&self, player: Player) -> MatchInfo {
+        let (width, height) = self.view_size(player);
+        MatchInfo {
+            version: PROTOCOL_VERSION,
+            width,
+            height,
+            moves_per_turn: self.arena.moves_per_turn,
+            turn_limit: self.arena.turn_limit,
+            first: player == Player::A,
+            start: self.to_view(player, self.arena.start(player)),
+            opponent_start: self.to_view(player, self.arena.start(player.opponent())),
+        }
+    }
+
+    /// Converts a position on the real board to the view of `player`.
+    pub fn to_view(&self, player: Player, pos: Position) -> Position {
+        match player {
+            Player::A => pos,
+            Player::B => self.rotate(pos),
+        }
+    }
+
+    /// Converts a move made on the view of `player` to a position on the real board.
+    pub fn to_board(&self, player: Player, response: StrainOutput) -> Position {
+        match player {
+            Player::A => response,
+            Player::B => self.unrotate(response),
+        }
+    }
+
+    /// Claims `pos` (on the real board) for the active player and uses up one of their moves.
+    pub fn apply_move(&mut self, pos: Position) {
+        let value = Some(self.turn.as_bool());
+        self.board
+            .iter_mut()
+            .filter(|(k, _)| *k == pos)
+            .for_each(|(_, v)| *v = value);
+        self.last_moves[self.turn as usize].push(pos);
+        self.skips = 0;
+        self.advance();
+    }
+
+    /// Uses up one of the active player's moves without claiming anything.
+    /// The game ends once both players have skipped all of their moves in a row.
+    pub fn skip(&mut self) {
+        self.skips += 1;
+        if self.skips >= 2 * self.arena.moves_per_turn {
+            self.finished = true;
+        }
+        self.advance();
+    }
+
+    /// Ends the game early.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    fn advance(&mut self) {
+        self.move_in_turn += 1;
+        if self.move_in_turn >= self.arena.moves_per_turn {
+            self.move_in_turn = 0;
+            self.turn = self.turn.opponent();
+            self.last_moves[self.turn as usize].clear();
+            self.turns += 1;
+            if self
+                .arena
+                .turn_limit
+                .is_some_and(|limit| self.turns >= limit)
+            {
+                self.finished = true;
+            }
+        }
+    }
+
+    pub fn score(&self, player: Player) -> i32 {
+        let value = Some(player.as_bool());
+        self.board.iter().filter(|(_, v)| *v == value).count() as i32
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        let score_a = self.score(Player::A);
+        let score_b = self.score(Player::B);
+        if score_a > score_b {
+            Some(Player::A)
+        } else if score_b > score_a {
+            Some(Player::B)
+        } else {
+            None
+        }
+    }
+}
+
+/// Renders the real board one row per line, `A` and `B` for claimed cells and `.` for empty ones.
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.board.chunks(self.arena.width) {
+            for (_, v) in row {
+                let c = match v {
+                    Some(true) => 'A',
+                    Some(false) => 'B',
+                    None => '.',
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_of_player_b_round_trips_on_non_square_arenas() {
+        let state = GameState::with_arena(Arena::wide()).unwrap();
+        let (view_width, view_height) = state.view_size(Player::B);
+        assert_eq!((view_width, view_height), (3, 5));
+        for &(pos, _) in state.board() {
+            let rotated = state.rotate(pos);
+            assert!(
+                (0..view_width as i32).contains(&rotated.0)
+                    && (0..view_height as i32).contains(&rotated.1),
+                "{pos:?} was rotated off the view to {rotated:?}"
+            );
+            assert_eq!(state.unrotate(rotated), pos);
+            assert_eq!(
+                state.to_board(Player::B, state.to_view(Player::B, pos)),
+                pos
+            );
+        }
+    }
+
+    #[test]
+    fn both_players_see_themselves_at_the_top_left() {
+        let state = GameState::with_arena(Arena::wide()).unwrap();
+        for player in [Player::A, Player::B] {
+            let view = state.view(player);
+            assert!(view.contains(&((0, 0), Some(true))));
+            assert_eq!(view.iter().filter(|(_, v)| v.is_some()).count(), 2);
+        }
+        assert_eq!(state.match_info(Player::B).start, (0, 0));
+        assert_eq!(state.match_info(Player::B).opponent_start, (2, 4));
+    }
+
+    #[test]
+    fn game_ends_once_both_players_skip_all_their_moves() {
+        let mut state = GameState::with_arena(Arena::wide()).unwrap();
+        for _ in 0..3 {
+            state.skip();
+            assert!(!state.is_finished());
+        }
+        state.skip();
+        assert!(state.is_finished());
+    }
+
+    #[test]
+    fn a_move_resets_the_skips() {
+        let mut state = GameState::with_arena(Arena::wide()).unwrap();
+        state.skip();
+        state.apply_move((1, 0));
+        for _ in 0..3 {
+            state.skip();
+            assert!(!state.is_finished());
+        }
+        state.skip();
+        assert!(state.is_finished());
+        assert_eq!(state.score(Player::A), 2);
+        assert_eq!(state.winner(), Some(Player::A));
+    }
+
+    #[test]
+    fn turns_pass_between_players() {
+        let mut state = GameState::with_arena(Arena::wide()).unwrap();
+        state.apply_move((1, 0));
+        assert_eq!(state.active_player(), Player::A);
+        assert_eq!(state.move_in_turn(), 1);
+        state.apply_move((2, 0));
+        assert_eq!(state.active_player(), Player::B);
+        assert_eq!(state.turns(), 1);
+        assert_eq!(state.last_moves(Player::A), &[(1, 0), (2, 0)]);
+        assert!(state.last_moves(Player::B).is_empty());
+    }
+
+    #[test]
+    fn turn_limit_ends_the_game() {
+        let arena = Arena {
+            turn_limit: Some(2),
+            ..Arena::wide()
+        };
+        let mut state = GameState::with_arena(arena).unwrap();
+        for pos in [(1, 0), (2, 0), (3, 2)] {
+            state.apply_move(pos);
+            assert!(!state.is_finished());
+        }
+        state.apply_move((2, 2));
+        assert!(state.is_finished());
+        assert_eq!(state.turns(), 2);
+    }
+
+    #[test]
+    fn allowed_moves_are_next_to_friendly_cells() {
+        let state = GameState::with_arena(Arena::wide()).unwrap();
+        let mut allowed = GameState::allowed_moves(&state.view(Player::A));
+        allowed.sort();
+        assert_eq!(allowed, vec![(0, 1), (1, 0)]);
+    }
+}
@@ -0,0 +1,121 @@
+use codestrain_common::StrainInput;
+use thiserror::Error;
+use wasmparser::types::EntityType;
+use wasmparser::{ExternalKind, Parser, Payload, ValType, Validator};
+
+use crate::{Arena, GameState, Match, PluginProvider, ResourceLimits, StrainError};
+
+/// Module of the extism kernel, which every plugin imports from.
+const KERNEL_MODULE: &str = "extism:host/env";
+/// Module of the host functions strains are given.
+const HOST_MODULE: &str = "extism:host/user";
+/// The host functions strains are given, see [`PluginProvider`].
+const HOST_FUNCTIONS: [&str; 2] = ["codestrain_random", "codestrain_time"];
+/// Exports the engine calls if a strain has them.
+const OPTIONAL_EXPORTS: [&str; 3] = ["board_encoding", "init", "take_moves"];
+
+/// Ways a wasm module can fail to be a strain.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("Wasm is {size} bytes, the limit is {max}")]
+    TooLarge { size: usize, max: usize },
+    #[error("Not a valid wasm module: {0}")]
+    InvalidModule(String),
+    #[error("Missing export `{0}`")]
+    MissingExport(&'static str),
+    #[error("Export `{0}` is not an extism function, taking no parameters and returning an i32")]
+    InvalidExport(String),
+    #[error("Import `{module}.{name}` is not allowed")]
+    ForbiddenImport { module: String, name: String },
+    #[error("Strain failed its first move: {0}")]
+    SmokeTest(StrainError),
+}
+
+impl ValidationError {
+    /// Stable identifier of the kind of error.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TooLarge { .. } => "too_large",
+            Self::InvalidModule(_) => "invalid_module",
+            Self::MissingExport(_) => "missing_export",
+            Self::InvalidExport(_) => "invalid_export",
+            Self::ForbiddenImport { .. } => "forbidden_import",
+            Self::SmokeTest(_) => "smoke_test",
+        }
+    }
+}
+
+/// Checks that `wasm` is a strain the engine can play with: a valid module within the size
+/// limit, exporting `take_turn` and importing nothing but the extism kernel and the host functions.
+/// The strain then has to make the first move of a match on the default arena.
+pub fn validate_strain(wasm: &[u8], limits: &ResourceLimits) -> Result<(), ValidationError> {
+    if wasm.len() > limits.max_wasm_bytes {
+        return Err(ValidationError::TooLarge {
+            size: wasm.len(),
+            max: limits.max_wasm_bytes,
+        });
+    }
+    check_module(wasm)?;
+    smoke_test(wasm, limits).map_err(ValidationError::SmokeTest)
+}
+
+fn check_module(wasm: &[u8]) -> Result<(), ValidationError> {
+    let invalid = |e: wasmparser::BinaryReaderError| ValidationError::InvalidModule(e.to_string());
+    let types = Validator::new().validate_all(wasm).map_err(invalid)?;
+    let mut has_take_turn = false;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.map_err(invalid)? {
+            Payload::ImportSection(imports) => {
+                for import in imports {
+                    let import = import.map_err(invalid)?;
+                    let allowed = import.module == KERNEL_MODULE
+                        || (import.module == HOST_MODULE && HOST_FUNCTIONS.contains(&import.name));
+                    if !allowed {
+                        return Err(ValidationError::ForbiddenImport {
+                            module: import.module.to_string(),
+                            name: import.name.to_string(),
+                        });
+                    }
+                }
+            }
+            Payload::ExportSection(exports) => {
+                for export in exports {
+                    let export = export.map_err(invalid)?;
+                    if export.name != "take_turn" && !OPTIONAL_EXPORTS.contains(&export.name) {
+                        continue;
+                    }
+                    let is_plugin_fn = export.kind == ExternalKind::Func
+                        && match types.entity_type_from_export(&export) {
+                            Some(EntityType::Func(id)) => {
+                                let ty = types[id].unwrap_func();
+                                ty.params().is_empty() && ty.results() == [ValType::I32]
+                            }
+                            _ => false,
+                        };
+                    if !is_plugin_fn {
+                        return Err(ValidationError::InvalidExport(export.name.to_string()));
+                    }
+                    has_take_turn |= export.name == "take_turn";
+                }
+            }
+            _ => {}
+        }
+    }
+    if !has_take_turn {
+        return Err(ValidationError::MissingExport("take_turn"));
+    }
+    Ok(())
+}
+
+/// Plays the first move of a match against a strain that takes the first allowed move.
+fn smoke_test(wasm: &[u8], limits: &ResourceLimits) -> Result<(), StrainError> {
+    let strain =
+        PluginProvider::new(wasm, limits, 0).map_err(|e| StrainError::LoadFailed(e.to_string()))?;
+    let state = GameState::with_arena(Arena::default()).expect("Default arena is invalid");
+    let mut game = Match::new(state, strain, |input: StrainInput| input.allowed[0]);
+    game.step();
+    match game.violations().first() {
+        Some(violation) => Err(violation.reason.clone()),
+        None => Ok(()),
+    }
+}
@@ -1,16 +1,80 @@
 import { env } from '$env/dynamic/public';
 export const BACKEND_URL = env.PUBLIC_BACKEND_URL || 'http://localhost:8000';
 
+export type Role = 'admin' | 'moderator' | 'author' | 'viewer';
+
 export type User = {
     id: string;
     name: string;
     email: string;
-    admin: boolean;
+    roles: Role[];
     password?: string;
     created_at: string;
     updated_at: string;
 }
 
+export type RatingSummary = {
+    /** Strains that have played a rated battle, the rest have no rating yet. */
+    rated_strains: number;
+    best: number | null;
+    average: number | null;
+    games: number;
+    wins: number;
+    draws: number;
+    losses: number;
+}
+
+/** What anyone can see of a user. */
+export type UserProfile = {
+    id: string;
+    name: string;
+    created_at: string;
+    strains: number;
+    rating: RatingSummary;
+}
+
+export type UserUpdate = {
+    current_password: string;
+    name?: string;
+    email?: string;
+    password?: string;
+}
+
+export async function get_user(id: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/user/${id}`);
+    if(!response.ok) throw new Error(`Failed to fetch user (${response.status})`);
+    return response.json() as Promise<UserProfile>;
+}
+
+/** Every user with their email, only for admins. */
+export async function get_users(token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/user`, {
+        headers: {
+            'Authorization': `Bearer ${token}`
+        }
+    });
+    if(!response.ok) throw new Error(`Failed to fetch users (${response.status})`);
+    return response.json() as Promise<User[]>;
+}
+
+export async function update_me(update: UserUpdate, token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/me`, {
+        method: 'PATCH',
+        headers: {
+            'Content-Type': 'application/json',
+            'Authorization': `Bearer ${token}`
+        },
+        body: JSON.stringify(update)
+    });
+    if(response.status === 401) throw new Error('Current password is wrong');
+    if(response.status === 409) throw new Error('Email is already in use');
+    if(!response.ok) throw new Error(`Failed to update account (${response.status})`);
+    return response.json() as Promise<User>;
+}
+
 export type Session = {
     id: string;
     creator_id: string;
@@ -19,11 +83,97 @@ export type Session = {
     updated_at: string;
 }
 
+/** A session as listed to its user, without the token. */
+export type SessionInfo = {
+    id: string;
+    created_at: string;
+    last_used_at: string;
+    expires_at: string;
+    /** Whether this is the session of the request. */
+    current: boolean;
+}
+
+export async function get_sessions(token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/me/sessions`, {
+        headers: {
+            'Authorization': `Bearer ${token}`
+        }
+    });
+    if(!response.ok) throw new Error(`Failed to fetch sessions (${response.status})`);
+    return response.json() as Promise<SessionInfo[]>;
+}
+
+export async function revoke_session(id: string, token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/me/sessions/${id}`, {
+        method: 'DELETE',
+        headers: {
+            'Authorization': `Bearer ${token}`
+        }
+    });
+    if(!response.ok) throw new Error(`Failed to revoke session (${response.status})`);
+}
+
+export type Scope = 'strains:write' | 'battles:run';
+
+export type ApiToken = {
+    id: string;
+    name: string;
+    scopes: Scope[];
+    created_at: string;
+    last_used_at: string | null;
+}
+
+/** The token is only given out once, when it is created. */
+export type CreatedApiToken = ApiToken & {
+    token: string;
+}
+
+export async function get_api_tokens(token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/me/tokens`, {
+        headers: {
+            'Authorization': `Bearer ${token}`
+        }
+    });
+    if(!response.ok) throw new Error(`Failed to fetch API tokens (${response.status})`);
+    return response.json() as Promise<ApiToken[]>;
+}
+
+export async function create_api_token(name: string, scopes: Scope[], token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/me/tokens`, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+            'Authorization': `Bearer ${token}`
+        },
+        body: JSON.stringify({name, scopes})
+    });
+    if(!response.ok) throw new Error(`Failed to create API token (${response.status}): ${await response.text()}`);
+    return response.json() as Promise<CreatedApiToken>;
+}
+
+export async function revoke_api_token(id: string, token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/me/tokens/${id}`, {
+        method: 'DELETE',
+        headers: {
+            'Authorization': `Bearer ${token}`
+        }
+    });
+    if(!response.ok) throw new Error(`Failed to revoke API token (${response.status})`);
+}
+
 export type Strain = {
     id: string;
     creator_id: string;
     name: string;
     description: string | null;
+    version: number;
+    /** Whether the wasm was compiled from the code by the server. */
+    built: boolean;
     created_at: string;
     updated_at: string;
 }
@@ -35,13 +185,14 @@ export async function get_strains(user_id?: string, custom_fetch?: typeof fetch)
     return response.json() as Promise<Strain[]>;
 }
 
-export async function create_strain(name: string, description: string | null, code: string, wasm: string, token: string, custom_fetch?: typeof fetch) {
+/** Leave out the wasm to have the server build it from the code. */
+export async function create_strain(name: string, description: string | null, code: string, wasm: string | null, token: string, custom_fetch?: typeof fetch) {
     const fetch = custom_fetch || window.fetch;
     const body = {
         name,
         description,
         code,
-        wasm
+        wasm: wasm ?? undefined
     };
     const response = await fetch(`${BACKEND_URL}/strain`, {
         method: 'POST',
@@ -51,7 +202,7 @@ export async function create_strain(name: string, description: string | null, co
         },
         body: JSON.stringify(body)
     });
-    if(!response.ok) throw new Error(`Failed to create strain (${response.status})`);
+    if(!response.ok) throw new Error(`Failed to create strain (${response.status}): ${await response.text()}`);
     return response.json() as Promise<Strain>;
 }
 
@@ -66,25 +217,66 @@ export async function delete_strain(id: string, token: string, custom_fetch?: ty
     if(!response.ok) throw new Error(`Failed to delete strain (${response.status})`);
 }
 
+export type Arena = {
+    width: number;
+    height: number;
+    moves_per_turn: number;
+    turn_limit: number | null;
+    start_a: [number, number];
+    start_b: [number, number];
+}
 export type BattleMeta = {
     id: string;
-    arena_size: number;
+    arena_id: string;
+    arena: Arena;
     strain_a: string;
     strain_b: string;
+    version_a: string;
+    version_b: string;
     winner: string | null;
     score_a: number;
     score_b: number;
 }
+export type BattleStatus = 'pending' | 'running' | 'finished' | 'failed';
+export type BattleJob = {
+    id: string;
+    arena_id: string;
+    arena: Arena;
+    version_a: string;
+    version_b: string;
+    status: Exclude<BattleStatus, 'finished'>;
+    error: string | null;
+    seed: number | null;
+    requested_by: string | null;
+    created_at: string;
+    started_at: string | null;
+    finished_at: string | null;
+}
 export type BattleResult = BattleMeta & {
+    status: 'finished';
+    seed: number | null;
+    forfeit: boolean | null;
     log: {
         player: boolean;
         x: number;
         y: number;
         allowed: boolean;
+        /** Microseconds the strain took to pick the move, null for battles from before it was recorded. */
+        duration_us: number | null;
+        /** Number of moves the strain could choose from. */
+        allowed_count: number | null;
+        /** Fuel, roughly wasm instructions, the strain used to pick the move. */
+        fuel: number | null;
+    }[];
+    violations: {
+        turn: number;
+        player: boolean;
+        reason: string;
+        message: string | null;
     }[];
 }
 
-export async function request_battle(strain_a: string, strain_b: string, token: string, custom_fetch?: typeof fetch) {
+export async function request_battle(strain_a: string, strain_b: string, token: string, custom_fetch?: typeof fetch, arena?: Partial<Arena>, seed?: number) {
     const fetch = custom_fetch || window.fetch;
     const response = await fetch(`${BACKEND_URL}/battle`, {
         method: 'POST',
@@ -92,17 +284,65 @@ export async function request_battle(strain_a: string, strain_b: string, token:
             'Content-Type': 'application/json',
             'Authorization': `Bearer ${token}`
         },
-        body: JSON.stringify({strain_a, strain_b})
+        body: JSON.stringify({strain_a, strain_b, arena, seed})
     });
     if(!response.ok) throw new Error(`Failed to request battle (${response.status})`);
-    return response.json() as Promise<BattleResult>;
+    return response.json() as Promise<BattleJob>;
 }
 
 export async function get_battle(id: string, custom_fetch?: typeof fetch) {
     const fetch = custom_fetch || window.fetch;
     const response = await fetch(`${BACKEND_URL}/battle/${id}`);
     if(!response.ok) throw new Error(`Failed to fetch battle (${response.status})`);
-    return response.json() as Promise<BattleResult>;
+    return response.json() as Promise<BattleResult | BattleJob>;
+}
+
+export type BattleOutcome = {
+    winner: string | null;
+    score_a: number;
+    score_b: number;
+    forfeit: boolean | null;
+    moves: number;
+}
+export type BattleVerification = {
+    id: string;
+    seed: number;
+    reproduced: boolean;
+    first_difference: number | null;
+    stored: BattleOutcome;
+    replayed: BattleOutcome;
+}
+
+/** Plays a finished battle again with its seed, to check that it plays out the same way. */
+export async function verify_battle(id: string, token: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/battle/${id}/verify`, {
+        method: 'POST',
+        headers: {
+            'Authorization': `Bearer ${token}`
+        }
+    });
+    if(!response.ok) throw new Error(`Failed to verify battle (${response.status})`);
+    return response.json() as Promise<BattleVerification>;
+}
+
+export type BattleEvent =
+    | { type: 'move'; turn: number; player: boolean; x: number; y: number; allowed: boolean; score_a: number; score_b: number }
+    | { type: 'finished'; winner: string | null; score_a: number; score_b: number; forfeit: boolean | null }
+    | { type: 'failed'; error: string };
+
+/** Follows a battle move by move while it is being played. Call `close` on the result to stop early. */
+export function stream_battle(id: string, on_event: (event: BattleEvent) => void) {
+    const source = new EventSource(`${BACKEND_URL}/battle/${id}/stream`);
+    const handle = (message: MessageEvent) => {
+        const event = JSON.parse(message.data) as BattleEvent;
+        if(event.type !== 'move') source.close();
+        on_event(event);
+    };
+    source.addEventListener('move', handle);
+    source.addEventListener('finished', handle);
+    source.addEventListener('failed', handle);
+    return source;
 }
 
 export async function get_battles(custom_fetch?: typeof fetch) {
@@ -112,6 +352,38 @@ export async function get_battles(custom_fetch?: typeof fetch) {
     return response.json() as Promise<BattleMeta[]>;
 }
 
+export type PracticeMatch = {
+    opponent: string;
+    /** Whether the tested strain moved first. */
+    first: boolean;
+    /** Whether the tested strain won, null on a draw. */
+    won: boolean | null;
+    score: number;
+    opponent_score: number;
+    forfeit: boolean | null;
+    log: BattleResult['log'];
+    violations: BattleResult['violations'];
+}
+export type PracticeResult = {
+    seed: number;
+    matches: PracticeMatch[];
+}
+
+/** Plays an unsaved strain against the strains built into the server, without storing anything. */
+export async function test_strain(wasm: string, token: string, custom_fetch?: typeof fetch, opponents?: string[]) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/strain/test`, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+            'Authorization': `Bearer ${token}`
+        },
+        body: JSON.stringify({wasm, opponents})
+    });
+    if(!response.ok) throw new Error(`Failed to test strain (${response.status}): ${await response.text()}`);
+    return response.json() as Promise<PracticeResult>;
+}
+
 export type StrainWithExtra = Strain & {
     code: string;
     wasm_size: number;
@@ -125,3 +397,69 @@ export async function get_strain(id: string, custom_fetch?: typeof fetch) {
     if(!response.ok) throw new Error(`Failed to fetch strain (${response.status})`);
     return response.json() as Promise<StrainWithExtra>;
 }
+
+export type Tournament = {
+    id: string;
+    arena_id: string;
+    arena: Arena;
+    created_by: string | null;
+    created_at: string;
+    finished_at: string | null;
+}
+
+export type Standing = {
+    strain_id: string;
+    version_id: string;
+    name: string;
+    played: number;
+    wins: number;
+    draws: number;
+    losses: number;
+    points: number;
+    score_for: number;
+    score_against: number;
+}
+
+export type TournamentStandings = Tournament & {
+    standings: Standing[];
+    battles: string[];
+}
+
+export async function get_tournaments(custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/tournament`);
+    if(!response.ok) throw new Error(`Failed to fetch tournaments (${response.status})`);
+    return response.json() as Promise<Tournament[]>;
+}
+
+export async function get_tournament(id: string, custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/tournament/${id}`);
+    if(!response.ok) throw new Error(`Failed to fetch tournament (${response.status})`);
+    return response.json() as Promise<TournamentStandings>;
+}
+
+export type RatingPoint = {
+    rating: number;
+    version_id: string | null;
+    battle_id: string | null;
+    created_at: string;
+}
+
+export type LeaderboardEntry = {
+    strain_id: string;
+    name: string;
+    rating: number;
+    games: number;
+    wins: number;
+    draws: number;
+    losses: number;
+    history: RatingPoint[];
+}
+
+export async function get_leaderboard(custom_fetch?: typeof fetch) {
+    const fetch = custom_fetch || window.fetch;
+    const response = await fetch(`${BACKEND_URL}/leaderboard`);
+    if(!response.ok) throw new Error(`Failed to fetch leaderboard (${response.status})`);
+    return response.json() as Promise<LeaderboardEntry[]>;
+}
@@ -1,6 +1,47 @@
+export const PROTOCOL_VERSION = 3;
+
+export type BoardEncoding = 'cells' | 'grid';
+
+// row-major, '.' for empty, 'x' for friendly and 'o' for enemy cells
+export type Board = {
+    width: number,
+    height: number,
+    cells: string
+};
+
 export type StrainInput = {
     board: [[number, number], boolean | null][],
-    allowed: [number, number][]
+    allowed: [number, number][],
+    version: number,
+    width: number,
+    height: number,
+    turn: number,
+    move_in_turn: number,
+    moves_left: number,
+    moves_per_turn: number,
+    opponent_moves: [number, number][],
+    friendly: number,
+    enemy: number,
+    grid?: Board,
+    state?: string
 };
 
-export type StrainOutput = [number, number];
\ No newline at end of file
+export type MatchInfo = {
+    version: number,
+    width: number,
+    height: number,
+    moves_per_turn: number,
+    turn_limit: number | null,
+    first: boolean,
+    start: [number, number],
+    opponent_start: [number, number]
+};
+
+export type StrainOutput = [number, number];
+
+// a move on its own keeps the state, `null` clears it
+export type StrainResponse = StrainOutput | { move: StrainOutput, state: string | null };
+
+
+// the moves of the rest of the turn, at most `moves_left` of them
+export type TurnResponse = StrainOutput[] | { moves: StrainOutput[], state: string | null };
\ No newline at end of file
@@ -1,10 +1,13 @@
-import { BACKEND_URL, type User } from '$lib/backend';
+import { get_users, type User } from '$lib/backend';
 import type { PageLoad } from './$types';
 
-export const load = (async ({fetch}) => {
-    const users_response = await fetch(`${BACKEND_URL}/user`);
-    if(!users_response.ok) throw new Error('Failed to fetch users');
-    const users: User[] = await users_response.json();
+export const load = (async ({fetch, parent}) => {
+    const data = await parent();
+    // only admins may list the users
+    let users: User[] = [];
+    if(data.session && data.user?.roles.includes('admin')) {
+        users = await get_users(data.session, fetch);
+    }
     return {
         users
     };
@@ -0,0 +1,19 @@
+import { verify_battle } from "$lib/backend";
+import { fail } from "@sveltejs/kit";
+import type { Actions } from "./$types";
+
+export const actions = {
+    verify: async ({ params, cookies }) => {
+        const token = cookies.get('session');
+        try {
+            return { verification: await verify_battle(params.id, token!, fetch) };
+        }
+        catch(e) {
+            console.error(e);
+            return fail(500, {
+                message: 'Failed to verify battle',
+                error: true
+            });
+        }
+    }
+} satisfies Actions;
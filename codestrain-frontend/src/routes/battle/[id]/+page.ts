@@ -3,10 +3,20 @@ import type { PageLoad } from './$types';
 
 export const load = (async ({params, fetch}) => {
     const battle = await get_battle(params.id, fetch);
+    if (battle.status !== 'finished') {
+        // not played yet, the page follows it live
+        return {
+            battle: null,
+            job: battle,
+            strain_a: null,
+            strain_b: null
+        };
+    }
     const strain_a = await get_strain(battle.strain_a, fetch);
     const strain_b = await get_strain(battle.strain_b, fetch);
     return {
         battle,
+        job: null,
         strain_a,
         strain_b
     };
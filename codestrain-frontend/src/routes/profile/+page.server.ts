@@ -0,0 +1,103 @@
+import { create_api_token, revoke_api_token, revoke_session, update_me, type Scope } from '$lib/backend';
+import { fail } from '@sveltejs/kit';
+import type { Actions } from './$types';
+
+export const actions = {
+	account: async ({request, cookies, fetch}) => {
+        const data = await request.formData();
+        const current_password = data.get('current_password');
+        const name = data.get('name');
+        const email = data.get('email');
+        const password = data.get('password');
+
+        if(!current_password) {
+            return fail(400, {
+                message: 'Current password is required',
+                error: true
+            });
+        }
+
+        try {
+            await update_me({
+                current_password: current_password + "",
+                name: name ? name + "" : undefined,
+                email: email ? email + "" : undefined,
+                password: password ? password + "" : undefined
+            }, cookies.get('session')!, fetch);
+        }
+        catch(e) {
+            console.error(e);
+            return fail(400, {
+                message: e instanceof Error ? e.message : 'Failed to update account',
+                error: true
+            });
+        }
+        return {
+            message: 'Account updated'
+        };
+	},
+	revoke_session: async ({request, cookies, fetch}) => {
+        const data = await request.formData();
+        const id = data.get('id');
+        if(!id) {
+            return fail(400, {
+                message: 'Session id is required',
+                error: true
+            });
+        }
+        try {
+            await revoke_session(id + "", cookies.get('session')!, fetch);
+        }
+        catch(e) {
+            console.error(e);
+            return fail(500, {
+                message: 'Failed to revoke session',
+                error: true
+            });
+        }
+	},
+	create_token: async ({request, cookies, fetch}) => {
+        const data = await request.formData();
+        const name = data.get('name');
+        const scopes = data.getAll('scopes').map((scope) => scope + "" as Scope);
+        if(!name || scopes.length === 0) {
+            return fail(400, {
+                message: 'Name and at least one scope are required',
+                error: true
+            });
+        }
+        try {
+            const created = await create_api_token(name + "", scopes, cookies.get('session')!, fetch);
+            return {
+                created_token: created.token
+            };
+        }
+        catch(e) {
+            console.error(e);
+            return fail(500, {
+                message: e instanceof Error ? e.message : 'Failed to create API token',
+                error: true
+            });
+        }
+	},
+	revoke_token: async ({request, cookies, fetch}) => {
+        const data = await request.formData();
+        const id = data.get('id');
+        if(!id) {
+            return fail(400, {
+                message: 'Token id is required',
+                error: true
+            });
+        }
+        try {
+            await revoke_api_token(id + "", cookies.get('session')!, fetch);
+        }
+        catch(e) {
+            console.error(e);
+            return fail(500, {
+                message: 'Failed to revoke API token',
+                error: true
+            });
+        }
+	}
+} satisfies Actions;
@@ -1,14 +1,20 @@
-import { get_strains } from '$lib/backend';
+import { get_api_tokens, get_sessions, get_strains } from '$lib/backend';
 import type { PageLoad } from './$types';
 
 export const load = (async ({parent, fetch}) => {
     const data = await parent();
     if(!("user" in data) || !data.user) return {};
-    const user_strains = await get_strains(data.user.id, fetch);
+    const [user_strains, sessions, api_tokens] = await Promise.all([
+        get_strains(data.user.id, fetch),
+        get_sessions(data.session, fetch),
+        get_api_tokens(data.session, fetch)
+    ]);
     return {
         user: {
             ...data.user,
             strains: user_strains
-        }
+        },
+        sessions,
+        api_tokens
     }
 }) satisfies PageLoad;
\ No newline at end of file
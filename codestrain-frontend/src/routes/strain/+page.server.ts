@@ -19,21 +19,22 @@ export const actions = {
         }
 
 
-        if(!code || !wasm) {
+        // without wasm the server builds the code
+        if(!code) {
             return fail(400, {
-                message: 'code and wasm are required',
+                message: 'code is required',
                 error: true
             });
         }
 
         let result;
         try {
-             result = await create_strain(name + "", description ? description + "" : null, code + "", wasm + "", cookies.get('session')!, fetch);
+             result = await create_strain(name + "", description ? description + "" : null, code + "", wasm ? wasm + "" : null, cookies.get('session')!, fetch);
         }
         catch(e) {
             console.error(e);
             return fail(500, {
-                message: 'Failed to create strain',
+                message: e instanceof Error ? e.message : 'Failed to create strain',
                 error: true
             });
         }
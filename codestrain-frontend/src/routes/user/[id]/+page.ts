@@ -0,0 +1,13 @@
+import { get_strains, get_user } from '$lib/backend';
+import type { PageLoad } from './$types';
+
+export const load = (async ({fetch, params}) => {
+    const [profile, strains] = await Promise.all([
+        get_user(params.id, fetch),
+        get_strains(params.id, fetch)
+    ]);
+    return {
+        profile,
+        strains
+    };
+}) satisfies PageLoad;
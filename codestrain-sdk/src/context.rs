@@ -0,0 +1,92 @@
+use codestrain_common::{Board, StrainInput};
+
+use crate::{distance_map, flood_fill, frontier, DistanceMap, Position};
+
+/// Everything known about the game when a move has to be made, from the side of the strain.
+pub struct TurnContext {
+    input: StrainInput,
+    board: Board,
+}
+
+impl TurnContext {
+    pub fn new(input: StrainInput) -> Self {
+        let board = input.to_grid();
+        Self { input, board }
+    }
+
+    /// The input as it was sent by the engine.
+    pub fn input(&self) -> &StrainInput {
+        &self.input
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The moves that can be made, never empty.
+    pub fn allowed(&self) -> &[Position] {
+        &self.input.allowed
+    }
+
+    pub fn is_allowed(&self, pos: Position) -> bool {
+        self.input.allowed.contains(&pos)
+    }
+
+    pub fn width(&self) -> usize {
+        self.board.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.board.height()
+    }
+
+    /// Number of completed turns, of both players.
+    pub fn turn(&self) -> usize {
+        self.input.turn
+    }
+
+    /// Number of moves left this turn, including this one.
+    pub fn moves_left(&self) -> usize {
+        self.input.moves_left
+    }
+
+    /// The moves the opponent made in its last turn, in order.
+    pub fn opponent_moves(&self) -> &[Position] {
+        &self.input.opponent_moves
+    }
+
+    pub fn friendly(&self) -> impl Iterator<Item = Position> + '_ {
+        self.cells(Some(true))
+    }
+
+    pub fn enemy(&self) -> impl Iterator<Item = Position> + '_ {
+        self.cells(Some(false))
+    }
+
+    fn cells(&self, cell: Option<bool>) -> impl Iterator<Item = Position> + '_ {
+        self.board
+            .iter()
+            .filter(move |(_, c)| *c == cell)
+            .map(|(pos, _)| pos)
+    }
+
+    /// Empty cells the opponent could claim next.
+    pub fn enemy_frontier(&self) -> Vec<Position> {
+        frontier(&self.board, false)
+    }
+
+    /// Empty cells connected to `start` through other empty cells.
+    pub fn flood_fill(&self, start: Position) -> Vec<Position> {
+        flood_fill(&self.board, start)
+    }
+
+    /// Steps from the nearest friendly cell to every cell, through empty cells.
+    pub fn distances_from_friendly(&self) -> DistanceMap {
+        distance_map(&self.board, self.friendly())
+    }
+
+    /// Steps from the nearest enemy cell to every cell, through empty cells.
+    pub fn distances_from_enemy(&self) -> DistanceMap {
+        distance_map(&self.board, self.enemy())
+    }
+}
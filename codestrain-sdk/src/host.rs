@@ -0,0 +1,73 @@
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "extism:host/user")]
+extern "C" {
+    fn codestrain_random() -> u64;
+    fn codestrain_time() -> u64;
+}
+
+/// A random number from the seed of the match, the same on every replay of it.
+///
+/// Strains have no other source of randomness, so this is the one to use.
+/// Outside of wasm, like in the built-in strains of the server, the numbers come from a fixed seed
+/// per thread instead.
+pub fn random() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return unsafe { codestrain_random() };
+    #[cfg(not(target_arch = "wasm32"))]
+    return native::random();
+}
+
+/// A random number below `bound`, which must not be 0.
+pub fn random_below(bound: usize) -> usize {
+    (random() % bound as u64) as usize
+}
+
+/// Milliseconds since the match started, as the engine counts them: every call into the strain
+/// takes a second and every read of the time a millisecond, however long they actually take.
+/// Outside of wasm it is the real time since the first read on the thread.
+pub fn time_ms() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return unsafe { codestrain_time() };
+    #[cfg(not(target_arch = "wasm32"))]
+    return native::time_ms();
+}
+
+/// Stand-ins for the host functions, which only the engine provides to plugins.
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    thread_local! {
+        static RNG: Cell<u64> = const { Cell::new(0) };
+        static STARTED: Instant = Instant::now();
+    }
+
+    /// SplitMix64, like the generator of the engine.
+    pub fn random() -> u64 {
+        let state = RNG.get().wrapping_add(0x9e3779b97f4a7c15);
+        RNG.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn time_ms() -> u64 {
+        STARTED.with(|started| started.elapsed().as_millis() as u64)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_random_is_the_same_on_every_thread() {
+        let numbers = || (0..10).map(|_| random()).collect::<Vec<_>>();
+        let first = numbers();
+        assert_eq!(first[0], 0xe220a8397b1dcdaf);
+        assert_eq!(std::thread::spawn(numbers).join().unwrap(), first);
+        assert!(random_below(3) < 3);
+    }
+}
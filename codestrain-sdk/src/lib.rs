@@ -0,0 +1,99 @@
+//! Everything needed to write a strain, without any of the plugin plumbing.
+//!
+//! ```ignore
+//! use codestrain_sdk::*;
+//!
+//! #[derive(Default)]
+//! struct FirstChoice;
+//!
+//! impl Strategy for FirstChoice {
+//!     fn take_turn(&mut self, ctx: &TurnContext) -> Move {
+//!         ctx.allowed()[0]
+//!     }
+//! }
+//!
+//! export_strain!(FirstChoice);
+//! ```
+
+use std::sync::Mutex;
+
+mod context;
+mod host;
+mod query;
+
+pub use codestrain_common::*;
+pub use context::*;
+pub use host::*;
+pub use query::*;
+
+#[doc(hidden)]
+pub use extism_pdk;
+
+/// A position on the board, `(x, y)`, as seen by the strain.
+pub type Position = (i32, i32);
+/// A move of the strain, the position of the cell it claims.
+pub type Move = StrainOutput;
+
+/// A way of playing the game.
+///
+/// A new strategy is created for every match, so it can keep whatever it wants between turns.
+pub trait Strategy: Default {
+    /// Called once before the first move of the match.
+    fn init(&mut self, _info: &MatchInfo) {}
+
+    /// Picks the next move, one of [`TurnContext::allowed`].
+    fn take_turn(&mut self, ctx: &TurnContext) -> Move;
+}
+
+#[doc(hidden)]
+pub fn run_init<S: Strategy>(strategy: &Mutex<Option<S>>, info: MatchInfo) {
+    let mut new = S::default();
+    new.init(&info);
+    *strategy.lock().unwrap() = Some(new);
+}
+
+#[doc(hidden)]
+pub fn run_turn<S: Strategy>(strategy: &Mutex<Option<S>>, input: StrainInput) -> Move {
+    let ctx = TurnContext::new(input);
+    strategy
+        .lock()
+        .unwrap()
+        // engines from before `init` don't call it
+        .get_or_insert_with(S::default)
+        .take_turn(&ctx)
+}
+
+/// Exports a [`Strategy`] as the strain of this crate, which must be built as a `cdylib`.
+///
+/// The exports only exist on wasm, so the crate can also be used as a library elsewhere, like the
+/// built-in strains of the server.
+#[macro_export]
+macro_rules! export_strain {
+    ($strategy:ty) => {
+        #[cfg(target_arch = "wasm32")]
+        mod __codestrain_strain {
+            use super::*;
+            use $crate::extism_pdk::{self, plugin_fn, FnResult, Json};
+
+            static STRATEGY: ::std::sync::Mutex<Option<$strategy>> = ::std::sync::Mutex::new(None);
+
+            #[plugin_fn]
+            pub fn board_encoding() -> FnResult<Json<$crate::BoardEncoding>> {
+                Ok(Json($crate::BoardEncoding::Grid))
+            }
+
+            #[plugin_fn]
+            pub fn init(Json(info): Json<$crate::MatchInfo>) -> FnResult<Json<Option<String>>> {
+                $crate::run_init(&STRATEGY, info);
+                Ok(Json(None))
+            }
+
+            #[plugin_fn]
+            pub fn take_turn(
+                Json(input): Json<$crate::StrainInput>,
+            ) -> FnResult<Json<$crate::StrainOutput>> {
+                Ok(Json($crate::run_turn(&STRATEGY, input)))
+            }
+        }
+    };
+}
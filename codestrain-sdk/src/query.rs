@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+
+use codestrain_common::Board;
+
+use crate::Position;
+
+/// Number of steps to every cell of a board, `None` for unreachable cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceMap {
+    width: usize,
+    height: usize,
+    distances: Vec<Option<u32>>,
+}
+
+impl DistanceMap {
+    pub fn get(&self, (x, y): Position) -> Option<u32> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        self.distances[y as usize * self.width + x as usize]
+    }
+
+    /// The reachable position of `positions` with the fewest steps, the first one on ties.
+    pub fn closest(&self, positions: &[Position]) -> Option<Position> {
+        positions
+            .iter()
+            .filter_map(|&pos| Some((self.get(pos)?, pos)))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, pos)| pos)
+    }
+}
+
+/// Empty cells adjacent (without diagonals) to a cell of `owner`, true for friendly and false for enemy.
+pub fn frontier(board: &Board, owner: bool) -> Vec<Position> {
+    board
+        .iter()
+        .filter(|(_, cell)| cell.is_none())
+        .map(|(pos, _)| pos)
+        .filter(|&(x, y)| {
+            board
+                .neighbors(x, y)
+                .any(|(nx, ny)| board.get(nx, ny) == Some(Some(owner)))
+        })
+        .collect()
+}
+
+/// Empty cells connected to `start` through other empty cells, `start` first.
+/// Empty if `start` is not an empty cell.
+pub fn flood_fill(board: &Board, start: Position) -> Vec<Position> {
+    if board.get(start.0, start.1) != Some(None) {
+        return vec![];
+    }
+    let mut seen = vec![false; board.width() * board.height()];
+    let index = |(x, y): Position| y as usize * board.width() + x as usize;
+    seen[index(start)] = true;
+    let mut cells = vec![start];
+    let mut i = 0;
+    while let Some(&(x, y)) = cells.get(i) {
+        i += 1;
+        for next in board.neighbors(x, y) {
+            if board.get(next.0, next.1) == Some(None) && !seen[index(next)] {
+                seen[index(next)] = true;
+                cells.push(next);
+            }
+        }
+    }
+    cells
+}
+
+/// Steps from the nearest of `sources` to every cell, moving only through empty cells.
+/// The sources themselves are at distance 0 whatever they contain, and claimed cells can be
+/// reached but not passed through.
+pub fn distance_map(board: &Board, sources: impl IntoIterator<Item = Position>) -> DistanceMap {
+    let width = board.width();
+    let mut distances = vec![None; width * board.height()];
+    let index = |(x, y): Position| y as usize * width + x as usize;
+    let mut queue = VecDeque::new();
+    for pos in sources {
+        if board.contains(pos.0, pos.1) && distances[index(pos)].is_none() {
+            distances[index(pos)] = Some(0);
+            queue.push_back((pos, true));
+        }
+    }
+    while let Some((pos, source)) = queue.pop_front() {
+        if !source && board.get(pos.0, pos.1) != Some(None) {
+            continue;
+        }
+        let distance = distances[index(pos)].unwrap();
+        for next in board.neighbors(pos.0, pos.1) {
+            if distances[index(next)].is_none() {
+                distances[index(next)] = Some(distance + 1);
+                queue.push_back((next, false));
+            }
+        }
+    }
+    DistanceMap {
+        width,
+        height: board.height(),
+        distances,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A board from rows of `x` (friendly), `o` (enemy) and `.` (empty) cells.
+    fn from_rows(rows: &[&str]) -> Board {
+        let mut board = Board::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    'x' => Some(true),
+                    'o' => Some(false),
+                    _ => None,
+                };
+                board.set(x as i32, y as i32, cell);
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn frontier_is_the_empty_cells_next_to_the_owner() {
+        let board = from_rows(&["x..", ".o.", "..."]);
+        assert_eq!(frontier(&board, true), vec![(1, 0), (0, 1)]);
+        assert_eq!(
+            frontier(&board, false),
+            vec![(1, 0), (0, 1), (2, 1), (1, 2)]
+        );
+        assert!(frontier(&from_rows(&["...", "..."]), true).is_empty());
+    }
+
+    #[test]
+    fn flood_fill_stops_at_claimed_cells() {
+        let board = from_rows(&["..x..", "..o..", "..x.."]);
+        let mut cells = flood_fill(&board, (0, 0));
+        assert_eq!(cells[0], (0, 0));
+        cells.sort();
+        assert_eq!(cells, vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn flood_fill_needs_an_empty_start() {
+        let board = from_rows(&["x.", ".."]);
+        assert!(flood_fill(&board, (0, 0)).is_empty());
+        assert!(flood_fill(&board, (2, 0)).is_empty());
+        assert!(flood_fill(&board, (-1, 0)).is_empty());
+    }
+
+    #[test]
+    fn claimed_cells_are_reached_but_not_passed() {
+        let board = from_rows(&[".x.", ".x.", ".x."]);
+        let distances = distance_map(&board, [(0, 0)]);
+        assert_eq!(distances.get((0, 2)), Some(2));
+        assert_eq!(distances.get((1, 0)), Some(1));
+        assert_eq!(distances.get((1, 2)), Some(3));
+        for y in 0..3 {
+            assert_eq!(distances.get((2, y)), None);
+        }
+    }
+
+    #[test]
+    fn sources_are_at_zero_whatever_they_contain() {
+        let board = from_rows(&["x..", "...", "..o"]);
+        let distances = distance_map(&board, [(0, 0)]);
+        assert_eq!(distances.get((0, 0)), Some(0));
+        assert_eq!(distances.get((1, 1)), Some(2));
+        assert_eq!(distances.get((2, 2)), Some(4));
+        let distances = distance_map(&board, [(2, 2)]);
+        assert_eq!(distances.get((0, 0)), Some(4));
+    }
+
+    #[test]
+    fn distances_are_to_the_nearest_source() {
+        let board = from_rows(&["....."]);
+        // sources off the board and repeated ones are ignored
+        let distances = distance_map(&board, [(0, 0), (4, 0), (9, 9), (0, 0)]);
+        let row = (0..5).map(|x| distances.get((x, 0))).collect::<Vec<_>>();
+        assert_eq!(row, vec![Some(0), Some(1), Some(2), Some(1), Some(0)]);
+        assert_eq!(distances.get((5, 0)), None);
+        assert_eq!(distances.get((0, -1)), None);
+        assert_eq!(distance_map(&board, []).get((0, 0)), None);
+    }
+
+    #[test]
+    fn closest_takes_the_first_of_the_nearest() {
+        let distances = distance_map(&from_rows(&["....."]), [(0, 0), (4, 0)]);
+        assert_eq!(distances.closest(&[(2, 0), (1, 0), (3, 0)]), Some((1, 0)));
+        assert_eq!(distances.closest(&[(3, 0), (1, 0)]), Some((3, 0)));
+        assert_eq!(distances.closest(&[]), None);
+    }
+
+    #[test]
+    fn closest_skips_unreachable_positions() {
+        let distances = distance_map(&from_rows(&[".x.", ".x.", ".x."]), [(0, 0)]);
+        assert_eq!(distances.closest(&[(2, 0), (0, 2), (7, 7)]), Some((0, 2)));
+        assert_eq!(distances.closest(&[(2, 0), (2, 2)]), None);
+    }
+}
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS battle_violations;
+ALTER TABLE battles DROP COLUMN IF EXISTS forfeit;
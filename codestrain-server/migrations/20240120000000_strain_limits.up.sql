@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE battles ADD COLUMN forfeit BOOLEAN;
+
+CREATE TABLE IF NOT EXISTS battle_violations (
+    battle_id UUID NOT NULL REFERENCES battles(id) ON DELETE CASCADE,
+    seq INT NOT NULL,
+    turn INT NOT NULL,
+    player boolean NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (battle_id, seq)
+);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE battle_violations DROP COLUMN IF EXISTS message;
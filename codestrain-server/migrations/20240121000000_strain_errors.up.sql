@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE battle_violations ADD COLUMN message TEXT;
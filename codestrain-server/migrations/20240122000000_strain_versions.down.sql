@@ -0,0 +1,36 @@
+-- Add down migration script here
+ALTER TABLE battles
+    DROP CONSTRAINT battles_version_a_version_b_arena_size_key,
+    DROP COLUMN version_a,
+    DROP COLUMN version_b;
+
+-- only the latest version of each strain survives
+DELETE FROM battles a
+USING battles b
+WHERE a.strain_a = b.strain_a AND a.strain_b = b.strain_b AND a.arena_size = b.arena_size
+    AND a.created_at < b.created_at;
+
+ALTER TABLE battles
+    ADD CONSTRAINT battles_strain_a_strain_b_arena_size_key UNIQUE (strain_a, strain_b, arena_size);
+
+ALTER TABLE strains
+    ADD COLUMN code TEXT,
+    ADD COLUMN wasm bytea,
+    ADD COLUMN wasm_hash TEXT;
+
+UPDATE strains
+SET code = latest.code, wasm = latest.wasm, wasm_hash = latest.wasm_hash
+FROM (
+    SELECT DISTINCT ON (strain_id) strain_id, code, wasm, wasm_hash
+    FROM strain_versions
+    ORDER BY strain_id, version DESC
+) latest
+WHERE latest.strain_id = strains.id;
+
+ALTER TABLE strains
+    ALTER COLUMN code SET NOT NULL,
+    ALTER COLUMN wasm SET NOT NULL,
+    ALTER COLUMN wasm_hash SET NOT NULL,
+    ADD CONSTRAINT strains_wasm_hash_key UNIQUE (wasm_hash);
+
+DROP TABLE IF EXISTS strain_versions;
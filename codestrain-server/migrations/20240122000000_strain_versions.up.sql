@@ -0,0 +1,35 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS strain_versions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    strain_id UUID NOT NULL REFERENCES strains(id) ON DELETE CASCADE,
+    version INT NOT NULL,
+    code TEXT NOT NULL,
+    wasm bytea NOT NULL,
+    wasm_hash TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (strain_id, version),
+    UNIQUE (wasm_hash)
+);
+
+INSERT INTO strain_versions (strain_id, version, code, wasm, wasm_hash, created_at)
+SELECT id, 1, code, wasm, wasm_hash, created_at
+FROM strains;
+
+ALTER TABLE strains
+    DROP COLUMN code,
+    DROP COLUMN wasm,
+    DROP COLUMN wasm_hash;
+
+ALTER TABLE battles
+    ADD COLUMN version_a UUID REFERENCES strain_versions(id) ON DELETE CASCADE,
+    ADD COLUMN version_b UUID REFERENCES strain_versions(id) ON DELETE CASCADE;
+
+UPDATE battles
+SET version_a = (SELECT id FROM strain_versions WHERE strain_id = battles.strain_a AND version = 1),
+    version_b = (SELECT id FROM strain_versions WHERE strain_id = battles.strain_b AND version = 1);
+
+ALTER TABLE battles
+    ALTER COLUMN version_a SET NOT NULL,
+    ALTER COLUMN version_b SET NOT NULL,
+    DROP CONSTRAINT battles_strain_a_strain_b_arena_size_key,
+    ADD CONSTRAINT battles_version_a_version_b_arena_size_key UNIQUE (version_a, version_b, arena_size);
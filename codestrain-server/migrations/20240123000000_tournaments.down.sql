@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS tournament_battles;
+DROP TABLE IF EXISTS tournament_entries;
+DROP TABLE IF EXISTS tournaments;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS tournaments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    arena_size INT NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS tournament_entries (
+    tournament_id UUID NOT NULL REFERENCES tournaments(id) ON DELETE CASCADE,
+    strain_id UUID NOT NULL REFERENCES strains(id) ON DELETE CASCADE,
+    version_id UUID NOT NULL REFERENCES strain_versions(id) ON DELETE CASCADE,
+    PRIMARY KEY (tournament_id, strain_id)
+);
+
+CREATE TABLE IF NOT EXISTS tournament_battles (
+    tournament_id UUID NOT NULL REFERENCES tournaments(id) ON DELETE CASCADE,
+    battle_id UUID NOT NULL REFERENCES battles(id) ON DELETE CASCADE,
+    PRIMARY KEY (tournament_id, battle_id)
+);
@@ -0,0 +1,21 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS ratings (
+    strain_id UUID PRIMARY KEY REFERENCES strains(id) ON DELETE CASCADE,
+    rating DOUBLE PRECISION NOT NULL DEFAULT 1500,
+    games INT NOT NULL DEFAULT 0,
+    wins INT NOT NULL DEFAULT 0,
+    draws INT NOT NULL DEFAULT 0,
+    losses INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS rating_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    strain_id UUID NOT NULL REFERENCES strains(id) ON DELETE CASCADE,
+    version_id UUID REFERENCES strain_versions(id) ON DELETE SET NULL,
+    battle_id UUID REFERENCES battles(id) ON DELETE SET NULL,
+    rating DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS rating_history_strain_id_idx ON rating_history (strain_id, created_at);
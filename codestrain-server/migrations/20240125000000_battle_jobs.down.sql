@@ -0,0 +1,13 @@
+-- Add down migration script here
+DELETE FROM tournament_battles
+WHERE battle_id NOT IN (SELECT id FROM battles);
+
+ALTER TABLE tournament_battles
+    DROP CONSTRAINT tournament_battles_battle_id_fkey,
+    ADD CONSTRAINT tournament_battles_battle_id_fkey FOREIGN KEY (battle_id) REFERENCES battles(id) ON DELETE CASCADE;
+
+ALTER TABLE battles
+    DROP CONSTRAINT battles_id_fkey;
+
+DROP TABLE IF EXISTS battle_jobs;
+DROP TYPE IF EXISTS battle_status;
@@ -0,0 +1,30 @@
+-- Add up migration script here
+CREATE TYPE battle_status AS ENUM ('pending', 'running', 'finished', 'failed');
+
+CREATE TABLE IF NOT EXISTS battle_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    arena_size INT NOT NULL,
+    version_a UUID NOT NULL REFERENCES strain_versions(id) ON DELETE CASCADE,
+    version_b UUID NOT NULL REFERENCES strain_versions(id) ON DELETE CASCADE,
+    status battle_status NOT NULL DEFAULT 'pending',
+    error TEXT,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS battle_jobs_queue_idx ON battle_jobs (created_at) WHERE status IN ('pending', 'running');
+
+-- every existing battle becomes a finished job
+INSERT INTO battle_jobs (id, arena_size, version_a, version_b, status, created_at, started_at, finished_at)
+SELECT id, arena_size, version_a, version_b, 'finished', created_at, created_at, created_at
+FROM battles;
+
+ALTER TABLE battles
+    ADD CONSTRAINT battles_id_fkey FOREIGN KEY (id) REFERENCES battle_jobs(id) ON DELETE CASCADE;
+
+-- tournaments link to jobs, so they can include battles that have not been played yet
+ALTER TABLE tournament_battles
+    DROP CONSTRAINT tournament_battles_battle_id_fkey,
+    ADD CONSTRAINT tournament_battles_battle_id_fkey FOREIGN KEY (battle_id) REFERENCES battle_jobs(id) ON DELETE CASCADE;
@@ -0,0 +1,39 @@
+-- Add down migration script here
+ALTER TABLE battle_jobs ADD COLUMN arena_size INT;
+ALTER TABLE battles ADD COLUMN arena_size INT;
+ALTER TABLE tournaments ADD COLUMN arena_size INT;
+
+-- only the width survives, other arena settings are lost
+UPDATE battle_jobs SET arena_size = (arenas.config->>'width')::int
+FROM arenas
+WHERE arenas.id = battle_jobs.arena_id;
+UPDATE battles SET arena_size = (arenas.config->>'width')::int
+FROM arenas
+WHERE arenas.id = battles.arena_id;
+UPDATE tournaments SET arena_size = (arenas.config->>'width')::int
+FROM arenas
+WHERE arenas.id = tournaments.arena_id;
+
+-- keep the latest battle of pairings that now collide
+DELETE FROM battle_jobs
+WHERE id IN (
+    SELECT a.id
+    FROM battles a
+    INNER JOIN battles b
+        ON a.version_a = b.version_a AND a.version_b = b.version_b AND a.arena_size = b.arena_size
+    WHERE a.created_at < b.created_at
+);
+
+ALTER TABLE battle_jobs
+    ALTER COLUMN arena_size SET NOT NULL,
+    DROP COLUMN arena_id;
+ALTER TABLE battles
+    ALTER COLUMN arena_size SET NOT NULL,
+    DROP CONSTRAINT battles_version_a_version_b_arena_id_key,
+    ADD CONSTRAINT battles_version_a_version_b_arena_size_key UNIQUE (version_a, version_b, arena_size),
+    DROP COLUMN arena_id;
+ALTER TABLE tournaments
+    ALTER COLUMN arena_size SET NOT NULL,
+    DROP COLUMN arena_id;
+
+DROP TABLE IF EXISTS arenas;
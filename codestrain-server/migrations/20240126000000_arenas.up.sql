@@ -0,0 +1,48 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS arenas (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    config JSONB NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- every board size used so far was square, with 5 moves per turn and the players in opposite corners
+INSERT INTO arenas (config)
+SELECT DISTINCT jsonb_build_object(
+    'width', arena_size,
+    'height', arena_size,
+    'moves_per_turn', 5,
+    'turn_limit', NULL,
+    'start_a', jsonb_build_array(0, 0),
+    'start_b', jsonb_build_array(arena_size - 1, arena_size - 1)
+)
+FROM (
+    SELECT arena_size FROM battle_jobs
+    UNION
+    SELECT arena_size FROM tournaments
+) AS sizes;
+
+ALTER TABLE battle_jobs ADD COLUMN arena_id UUID REFERENCES arenas(id);
+ALTER TABLE battles ADD COLUMN arena_id UUID REFERENCES arenas(id);
+ALTER TABLE tournaments ADD COLUMN arena_id UUID REFERENCES arenas(id);
+
+UPDATE battle_jobs SET arena_id = arenas.id
+FROM arenas
+WHERE (arenas.config->>'width')::int = battle_jobs.arena_size;
+UPDATE battles SET arena_id = arenas.id
+FROM arenas
+WHERE (arenas.config->>'width')::int = battles.arena_size;
+UPDATE tournaments SET arena_id = arenas.id
+FROM arenas
+WHERE (arenas.config->>'width')::int = tournaments.arena_size;
+
+ALTER TABLE battle_jobs
+    ALTER COLUMN arena_id SET NOT NULL,
+    DROP COLUMN arena_size;
+ALTER TABLE battles
+    ALTER COLUMN arena_id SET NOT NULL,
+    DROP CONSTRAINT battles_version_a_version_b_arena_size_key,
+    ADD CONSTRAINT battles_version_a_version_b_arena_id_key UNIQUE (version_a, version_b, arena_id),
+    DROP COLUMN arena_size;
+ALTER TABLE tournaments
+    ALTER COLUMN arena_id SET NOT NULL,
+    DROP COLUMN arena_size;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE battle_jobs DROP COLUMN seed;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- battles played before seeds were introduced can't be re-run, so their seed stays NULL
+ALTER TABLE battle_jobs ADD COLUMN seed BIGINT;
+
+-- jobs that have not been played yet get one like any new job
+UPDATE battle_jobs
+SET seed = floor(random() * 9007199254740992)::BIGINT
+WHERE status IN ('pending', 'running');
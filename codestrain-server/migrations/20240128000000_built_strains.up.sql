@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE strain_versions ADD COLUMN built BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE battle_logs
+    DROP COLUMN duration_us,
+    DROP COLUMN allowed_count,
+    DROP COLUMN fuel,
+    DROP COLUMN memory_bytes;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- moves stored before this have no telemetry, so it is all nullable
+ALTER TABLE battle_logs
+    ADD COLUMN duration_us BIGINT,
+    ADD COLUMN allowed_count INT,
+    ADD COLUMN fuel BIGINT,
+    ADD COLUMN memory_bytes BIGINT;
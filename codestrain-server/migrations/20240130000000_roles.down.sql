@@ -0,0 +1,9 @@
+-- Add down migration script here
+ALTER TABLE users ADD COLUMN admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users
+SET admin = TRUE
+WHERE id IN (SELECT user_id FROM user_roles WHERE role = 'admin');
+
+DROP TABLE user_roles;
+DROP TYPE user_role;
@@ -0,0 +1,22 @@
+-- Add up migration script here
+CREATE TYPE user_role AS ENUM ('admin', 'moderator', 'author', 'viewer');
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role user_role NOT NULL,
+    granted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    granted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, role)
+);
+
+-- everyone could upload strains before roles existed
+INSERT INTO user_roles (user_id, role)
+SELECT id, 'author'
+FROM users;
+
+INSERT INTO user_roles (user_id, role)
+SELECT id, 'admin'
+FROM users
+WHERE admin;
+
+ALTER TABLE users DROP COLUMN admin;
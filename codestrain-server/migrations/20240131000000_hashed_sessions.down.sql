@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX sessions_expires_at;
+DROP INDEX sessions_user_id;
+
+ALTER TABLE sessions DROP COLUMN last_used_at;
+ALTER TABLE sessions ALTER COLUMN expires_at SET DEFAULT NOW() + INTERVAL '1 day';
+
+-- the tokens can't be recovered from their hashes
+DELETE FROM sessions;
+ALTER TABLE sessions ALTER COLUMN token_hash TYPE VARCHAR(255);
+ALTER TABLE sessions RENAME COLUMN token_hash TO token;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- only a hash of the token is kept, so a leaked database can't be used to log in
+ALTER TABLE sessions RENAME COLUMN token TO token_hash;
+ALTER TABLE sessions ALTER COLUMN token_hash TYPE TEXT;
+UPDATE sessions SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
+
+-- the server sets the expiry from its configured session ttl
+ALTER TABLE sessions ALTER COLUMN expires_at DROP DEFAULT;
+ALTER TABLE sessions ADD COLUMN last_used_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+CREATE INDEX sessions_user_id ON sessions (user_id);
+CREATE INDEX sessions_expires_at ON sessions (expires_at);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS api_tokens;
+DROP TYPE IF EXISTS api_token_scope;
@@ -0,0 +1,15 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS battles_version_a_version_b_arena_id_idx;
+
+-- keep the latest battle of pairings that were played more than once
+DELETE FROM battle_jobs
+WHERE id IN (
+    SELECT a.id
+    FROM battles a
+    INNER JOIN battles b
+        ON a.version_a = b.version_a AND a.version_b = b.version_b AND a.arena_id = b.arena_id
+    WHERE a.created_at < b.created_at
+);
+
+ALTER TABLE battles
+    ADD CONSTRAINT battles_version_a_version_b_arena_id_key UNIQUE (version_a, version_b, arena_id);
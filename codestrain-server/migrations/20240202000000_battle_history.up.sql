@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- every battle keeps its result, playing a pairing again adds another one
+ALTER TABLE battles DROP CONSTRAINT battles_version_a_version_b_arena_id_key;
+CREATE INDEX battles_version_a_version_b_arena_id_idx ON battles (version_a, version_b, arena_id);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- extism has no way to read the memory size of a plugin, so this was never filled in
+ALTER TABLE battle_logs DROP COLUMN memory_bytes;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- renewed while a worker plays the battle, so started_at can stay the time it was started
+ALTER TABLE battle_jobs ADD COLUMN lease_expires_at TIMESTAMP;
+UPDATE battle_jobs SET lease_expires_at = started_at WHERE status = 'running';
@@ -0,0 +1,72 @@
+use codestrain_engine::Arena;
+use rocket::serde::Deserialize;
+use rocket_db_pools::sqlx;
+
+use sqlx::types::Json;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Limits on the arenas battles can be requested with, configurable through the rocket config
+/// (e.g. `ROCKET_ARENA_MAX_SIZE`).
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ArenaBounds {
+    arena_min_size: usize,
+    arena_max_size: usize,
+    arena_max_moves_per_turn: usize,
+    arena_max_turn_limit: usize,
+}
+
+impl Default for ArenaBounds {
+    fn default() -> Self {
+        Self {
+            arena_min_size: 2,
+            arena_max_size: 64,
+            arena_max_moves_per_turn: 20,
+            arena_max_turn_limit: 10_000,
+        }
+    }
+}
+
+impl ArenaBounds {
+    /// Checks that `arena` is playable and within the bounds, or says why it is not.
+    pub fn check(&self, arena: &Arena) -> Result<(), String> {
+        let sizes = self.arena_min_size..=self.arena_max_size;
+        if !sizes.contains(&arena.width) || !sizes.contains(&arena.height) {
+            return Err(format!(
+                "Board width and height must be between {} and {}",
+                self.arena_min_size, self.arena_max_size
+            ));
+        }
+        if arena.moves_per_turn > self.arena_max_moves_per_turn {
+            return Err(format!(
+                "At most {} moves per turn are allowed",
+                self.arena_max_moves_per_turn
+            ));
+        }
+        if arena.turn_limit.unwrap_or(0) > self.arena_max_turn_limit {
+            return Err(format!(
+                "The turn limit can be at most {}",
+                self.arena_max_turn_limit
+            ));
+        }
+        // after the bounds, which keep the size of the board from overflowing
+        arena.validate().map_err(|e| e.to_string())
+    }
+}
+
+/// Returns the id of `arena`, storing it first if it has not been used before.
+pub async fn arena_id(db: &mut PgConnection, arena: &Arena) -> sqlx::Result<Uuid> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO arenas (config)
+        VALUES ($1)
+        ON CONFLICT (config) DO UPDATE SET config = EXCLUDED.config
+        RETURNING id
+        "#,
+        Json(arena) as _
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row.id)
+}
@@ -0,0 +1,238 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket_authorization::oauth::OAuth;
+use rocket_authorization::Credential;
+use rocket_db_pools::sqlx;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::roles::{Permission, Role};
+use crate::sessions::{self, SessionConfig};
+use crate::tokens::{self, Scope};
+use crate::{Db, ErrorResponse, User};
+
+/// Ways a request can fail to authenticate, answered with a JSON [`ErrorResponse`] by the
+/// [`unauthorized`] and [`forbidden`] catchers.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid credentials")]
+    InvalidToken,
+    #[error("Session has expired")]
+    SessionExpired,
+    #[error("User is not admin")]
+    NotAdmin,
+    #[error("Missing permission {}", .0.as_str())]
+    MissingPermission(Permission),
+    #[error("API token lacks the scope for {}", .0.as_str())]
+    MissingScope(Permission),
+    #[error("API tokens can't be used here, log in instead")]
+    TokenNotAllowed,
+    #[error("Internal error")]
+    InternalError,
+}
+
+impl AuthError {
+    /// Stable identifier of the kind of error.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MissingToken => "missing_token",
+            Self::InvalidToken => "invalid_token",
+            Self::SessionExpired => "session_expired",
+            Self::NotAdmin => "not_admin",
+            Self::MissingPermission(_) => "missing_permission",
+            Self::MissingScope(_) => "missing_scope",
+            Self::TokenNotAllowed => "token_not_allowed",
+            Self::InternalError => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            Self::MissingToken | Self::InvalidToken | Self::SessionExpired => Status::Unauthorized,
+            Self::NotAdmin
+            | Self::MissingPermission(_)
+            | Self::MissingScope(_)
+            | Self::TokenNotAllowed => Status::Forbidden,
+            Self::InternalError => Status::InternalServerError,
+        }
+    }
+
+    /// Fails the request guard, leaving the error for the catchers.
+    pub fn fail<T>(self, req: &Request<'_>) -> Outcome<T, Self> {
+        req.local_cache(|| Some(self.clone()));
+        Outcome::Error((self.status(), self))
+    }
+}
+
+/// How the caller of a request authenticated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Session(Uuid),
+    /// A personal API token, which only grants the permissions of its scopes.
+    Token {
+        id: Uuid,
+        scopes: Vec<Scope>,
+    },
+}
+
+/// The user behind the bearer token, of either a session or an API token.
+/// Only [`crate::roles::Authorized`] accepts API tokens, everything else needs a session.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user: User,
+    pub source: Source,
+}
+
+/// The user of the session in the bearer token.
+/// Protected handlers take this (or [`AdminUser`], [`crate::roles::Authorized`]) instead of
+/// looking up the session themselves.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user: User,
+    pub session_id: Uuid,
+}
+
+/// A logged in user with the admin role.
+pub struct AdminUser(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Caller {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // resolved once, however many guards of the request need the user
+        let caller = req
+            .local_cache_async(async { caller(req).await })
+            .await
+            .clone();
+        match caller {
+            Ok(caller) => Outcome::Success(caller),
+            Err(e) => e.fail(req),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let caller = rocket::outcome::try_outcome!(req.guard::<Caller>().await);
+        match caller.source {
+            Source::Session(session_id) => Outcome::Success(Self {
+                user: caller.user,
+                session_id,
+            }),
+            Source::Token { .. } => AuthError::TokenNotAllowed.fail(req),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let AuthUser { user, .. } = rocket::outcome::try_outcome!(req.guard::<AuthUser>().await);
+        if !user.roles.contains(&Role::Admin) {
+            return AuthError::NotAdmin.fail(req);
+        }
+        Outcome::Success(Self(user))
+    }
+}
+
+async fn caller(req: &Request<'_>) -> Result<Caller, AuthError> {
+    let Outcome::Success(auth) = req.guard::<Credential<OAuth>>().await else {
+        return Err(AuthError::MissingToken);
+    };
+    let (Some(db), Some(config)) = (
+        req.rocket().state::<Db>(),
+        req.rocket().state::<SessionConfig>(),
+    ) else {
+        return Err(AuthError::InternalError);
+    };
+    let internal = |e: sqlx::Error| {
+        println!("{:?}", e);
+        AuthError::InternalError
+    };
+
+    let (user_id, source) = if tokens::is_api_token(&auth.token) {
+        let token = tokens::lookup(db, &auth.token)
+            .await
+            .map_err(internal)?
+            .ok_or(AuthError::InvalidToken)?;
+        (
+            token.user_id,
+            Source::Token {
+                id: token.id,
+                scopes: token.scopes,
+            },
+        )
+    } else {
+        let session = sqlx::query!(
+            r#"
+            SELECT id, user_id, expires_at > NOW() AS "active!"
+            FROM sessions
+            WHERE token_hash = $1
+            "#,
+            sessions::hash_token(&auth.token)
+        )
+        .fetch_optional(&**db)
+        .await
+        .map_err(internal)?
+        .ok_or(AuthError::InvalidToken)?;
+        if !session.active {
+            return Err(AuthError::SessionExpired);
+        }
+        sessions::renew(db, session.id, config)
+            .await
+            .map_err(internal)?;
+        (session.user_id, Source::Session(session.id))
+    };
+
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, name, email, password, created_at, updated_at,
+            ARRAY(SELECT role FROM user_roles WHERE user_id = users.id ORDER BY role) AS "roles!: Vec<Role>"
+        FROM users
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(&**db)
+    .await
+    .map_err(internal)?;
+    Ok(Caller { user, source })
+}
+
+#[rocket::catch(401)]
+pub fn unauthorized(req: &Request<'_>) -> Json<ErrorResponse> {
+    caught(req, "invalid_credentials", "Invalid credentials")
+}
+
+#[rocket::catch(403)]
+pub fn forbidden(req: &Request<'_>) -> Json<ErrorResponse> {
+    caught(req, "forbidden", "Not allowed")
+}
+
+/// The error of the failed guard, or the given one if the handler itself refused the request.
+fn caught(req: &Request<'_>, error: &'static str, message: &str) -> Json<ErrorResponse> {
+    match req.local_cache(|| None::<AuthError>) {
+        Some(e) => error_response(e),
+        None => Json(ErrorResponse {
+            error,
+            message: message.to_string(),
+        }),
+    }
+}
+
+fn error_response(e: &AuthError) -> Json<ErrorResponse> {
+    Json(ErrorResponse {
+        error: e.as_str(),
+        message: e.to_string(),
+    })
+}
@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::serde::Deserialize;
+use rocket::tokio::{self, sync::Mutex};
+use thiserror::Error;
+
+/// Target the strains are compiled for.
+const TARGET: &str = "wasm32-unknown-unknown";
+/// Name of the crate the source is compiled as.
+const CRATE_NAME: &str = "strain";
+/// Environment variables passed on to the build, everything else is cleared.
+const PASSED_ENV: [&str; 5] = [
+    "PATH",
+    "HOME",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+];
+/// At most this much of the compiler output is returned on failure.
+const MAX_OUTPUT: usize = 16 * 1024;
+
+/// Settings of the strain build service, configurable through the rocket config
+/// (e.g. `ROCKET_STRAIN_BUILD_ENABLED`).
+///
+/// Disabled by default, as it needs a toolchain with the wasm target and every dependency of
+/// codestrain-sdk already downloaded, the build never touches the network.
+/// The source can read files at compile time (e.g. with `include_str!`), so builds can only be
+/// enabled together with a `strain_build_sandbox`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct BuildConfig {
+    strain_build_enabled: bool,
+    /// The codestrain-sdk crate strains are built against.
+    strain_build_sdk: PathBuf,
+    /// Shared between builds, so the dependencies are only compiled once.
+    strain_build_target_dir: PathBuf,
+    /// A command and its arguments that cargo is run through, e.g. `["bwrap", "--unshare-all", ...]`.
+    strain_build_sandbox: Vec<String>,
+    strain_build_timeout_ms: u64,
+    strain_build_max_source_bytes: usize,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            strain_build_enabled: false,
+            strain_build_sdk: PathBuf::from("../codestrain-sdk"),
+            strain_build_target_dir: std::env::temp_dir().join("codestrain-build"),
+            strain_build_sandbox: vec![],
+            strain_build_timeout_ms: 120_000,
+            strain_build_max_source_bytes: 256 * 1024,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BuildError {
+    #[error("Building strains is not enabled on this server")]
+    Disabled,
+    #[error("Source is larger than {0} bytes")]
+    TooLarge(usize),
+    #[error("Build timed out")]
+    TimedOut,
+    #[error("Build failed:\n{0}")]
+    Failed(String),
+    #[error("Failed to run the build: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Compiles strain source to wasm, one build at a time.
+pub struct Builder {
+    config: BuildConfig,
+    lock: Mutex<()>,
+}
+
+/// Reads the [`BuildConfig`] and manages a [`Builder`] with it.
+pub fn builder() -> impl Fairing {
+    AdHoc::try_on_ignite("Strain builder", |rocket| async {
+        let config = match rocket.figment().extract::<BuildConfig>() {
+            Ok(config) => config,
+            Err(e) => {
+                rocket::error!("Invalid strain build config: {}", e);
+                return Err(rocket);
+            }
+        };
+        if config.strain_build_enabled && config.strain_build_sandbox.is_empty() {
+            rocket::error!(
+                "Strain builds are enabled without a strain_build_sandbox to run them in"
+            );
+            return Err(rocket);
+        }
+        if config.strain_build_enabled {
+            println!(
+                "builder| building strains against {}",
+                config.strain_build_sdk.display()
+            );
+        }
+        Ok(rocket.manage(Builder {
+            config,
+            lock: Mutex::new(()),
+        }))
+    })
+}
+
+impl Builder {
+    /// Compiles `code` as the `lib.rs` of a strain crate depending on codestrain-sdk, and returns
+    /// the wasm.
+    pub async fn build(&self, code: &str) -> Result<Vec<u8>, BuildError> {
+        let config = &self.config;
+        if !config.strain_build_enabled {
+            return Err(BuildError::Disabled);
+        }
+        if code.len() > config.strain_build_max_source_bytes {
+            return Err(BuildError::TooLarge(config.strain_build_max_source_bytes));
+        }
+
+        let dir = tempfile::tempdir()?;
+        let sdk = config.strain_build_sdk.canonicalize()?;
+        tokio::fs::write(dir.path().join("Cargo.toml"), manifest(&sdk)).await?;
+        tokio::fs::create_dir(dir.path().join("src")).await?;
+        tokio::fs::write(dir.path().join("src/lib.rs"), code).await?;
+
+        // the target directory is shared, so the artifact must be read before the next build
+        let _guard = self.lock.lock().await;
+        let started = std::time::Instant::now();
+        let mut command = match config.strain_build_sandbox.split_first() {
+            Some((program, args)) => {
+                let mut command = tokio::process::Command::new(program);
+                command.args(args).arg("cargo");
+                command
+            }
+            None => tokio::process::Command::new("cargo"),
+        };
+        command
+            .args(["build", "--release", "--offline", "--target", TARGET])
+            .current_dir(dir.path())
+            .env_clear()
+            .envs(
+                PASSED_ENV
+                    .iter()
+                    .filter_map(|&key| Some((key, std::env::var_os(key)?))),
+            )
+            .env("CARGO_TARGET_DIR", &config.strain_build_target_dir)
+            .env("CARGO_TERM_COLOR", "never")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let output = tokio::time::timeout(
+            Duration::from_millis(config.strain_build_timeout_ms),
+            command.output(),
+        )
+        .await
+        .map_err(|_| BuildError::TimedOut)??;
+        if !output.status.success() {
+            return Err(BuildError::Failed(compiler_output(
+                &output.stderr,
+                dir.path(),
+            )));
+        }
+
+        let wasm = tokio::fs::read(
+            config
+                .strain_build_target_dir
+                .join(TARGET)
+                .join("release")
+                .join(format!("{CRATE_NAME}.wasm")),
+        )
+        .await?;
+        println!(
+            "builder| built {} bytes of wasm in {:?}",
+            wasm.len(),
+            started.elapsed()
+        );
+        Ok(wasm)
+    }
+}
+
+/// The manifest of the example strains, with the sdk at `sdk`.
+fn manifest(sdk: &Path) -> String {
+    format!(
+        r#"[package]
+name = "{CRATE_NAME}"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+codestrain-sdk = {{ path = {sdk:?} }}
+
+[workspace]
+"#
+    )
+}
+
+/// The end of the compiler output, without the paths of the build.
+fn compiler_output(stderr: &[u8], dir: &Path) -> String {
+    let output = String::from_utf8_lossy(stderr).replace(&*dir.to_string_lossy(), "");
+    let start = output.len().saturating_sub(MAX_OUTPUT);
+    let start = (start..output.len())
+        .find(|&i| output.is_char_boundary(i))
+        .unwrap_or(output.len());
+    output[start..].trim().to_string()
+}
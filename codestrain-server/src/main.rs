@@ -1,17 +1,35 @@
-use codestrain_common::*;
+mod arena;
+mod auth;
+mod compile;
+mod practice;
+mod queue;
+mod rating;
+mod roles;
+mod sessions;
+mod stream;
+mod tokens;
+mod tournament;
+mod verify;
+
+use arena::ArenaBounds;
+use auth::{AdminUser, AuthUser};
+use codestrain_engine::*;
+use compile::{BuildError, Builder};
+use queue::{BattleJob, BattleStatus};
+use roles::{Authorized, Permission, Role, RoleConfig};
+use sessions::SessionConfig;
+use stream::{BattleEvent, BattleStreams};
 
-use extism::*;
 use rocket::fairing::AdHoc;
 use rocket::http::Status;
-use rocket::response::status::Created;
+use rocket::response::status::{Accepted, Created};
 use rocket::serde::Serialize;
 use rocket::serde::{json::Json, Deserialize};
-use rocket::{routes, Config};
-use rocket_authorization::oauth::OAuth;
-use rocket_authorization::Credential;
+use rocket::{routes, Config, State};
 use rocket_db_pools::{sqlx, Connection, Database};
 
 use futures::stream::TryStreamExt;
+use std::time::Duration;
 use thiserror::Error;
 use time::PrimitiveDateTime;
 use uuid::Uuid;
@@ -32,8 +50,8 @@ type CoolerResult<T, E = rocket::response::Debug<sqlx::Error>> = std::result::Re
 struct User {
     #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     id: Option<Uuid>,
-    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
-    admin: Option<bool>,
+    #[serde(skip_deserializing, default)]
+    roles: Vec<Role>,
     name: String,
     #[serde(skip_serializing)]
     password: String,
@@ -54,6 +72,10 @@ pub enum NewUserError {
     #[error("User already exists")]
     #[from(sqlx::Error)]
     UserExists,
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+    #[error("Email address is reserved for an admin")]
+    ReservedEmail,
     #[error("Internal error")]
     InternalError,
 }
@@ -69,6 +91,8 @@ impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for NewUserError {
             Self::InvalidPassword => Status::BadRequest,
             Self::InvalidName => Status::BadRequest,
             Self::UserExists => Status::Conflict,
+            Self::InvalidCredentials => Status::Unauthorized,
+            Self::ReservedEmail => Status::Forbidden,
             // in our simplistic example, we're happy to respond with the default 500 responder in all cases
             _ => Status::InternalServerError,
         }
@@ -80,34 +104,43 @@ impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for NewUserError {
 async fn new_user(
     mut db: Connection<Db>,
     mut new_user: Json<User>,
+    role_config: &State<RoleConfig>,
 ) -> Result<Created<Json<User>>, NewUserError> {
-    let admin = new_user.email == "elias@ruta.fi";
-
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(new_user.password.as_bytes(), &salt)
-        .map_err(|_| NewUserError::InternalError)?
-        .to_string();
+    let password_hash = hash_password(&new_user.password)?;
 
+    let mut tx = sqlx::Connection::begin(&mut **db)
+        .await
+        .map_err(|_| NewUserError::InternalError)?;
     let r = sqlx::query!(
         r#"
-        INSERT INTO users (name, password, email, admin)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO users (name, password, email)
+        VALUES ($1, $2, $3)
         RETURNING id
         "#,
         new_user.name,
         password_hash,
-        new_user.email,
-        admin
+        new_user.email
     )
-    .fetch_optional(&mut **db)
+    .fetch_optional(&mut *tx)
     .await;
 
     match r {
         Ok(Some(row)) => {
+            let mut roles = vec![Role::DEFAULT];
+            roles::grant(&mut tx, row.id, Role::DEFAULT, None)
+                .await
+                .map_err(|_| NewUserError::InternalError)?;
+            if role_config.is_admin_email(&new_user.email)
+                && roles::bootstrap_admin(&mut tx, row.id)
+                    .await
+                    .map_err(|_| NewUserError::InternalError)?
+            {
+                roles.insert(0, Role::Admin);
+            }
+            tx.commit().await.map_err(|_| NewUserError::InternalError)?;
             // Set the new user's id from the returned value and return as before
             new_user.id = Some(row.id);
+            new_user.roles = roles;
             Ok(Created::new("/user").body(new_user))
         }
         Err(sqlx::Error::Database(e)) => {
@@ -121,12 +154,24 @@ async fn new_user(
     }
 }
 
+fn hash_password(password: &str) -> Result<String, NewUserError> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| NewUserError::InternalError)?
+        .to_string();
+    Ok(password_hash)
+}
+
+/// Every user with their email, for admins.
 #[rocket::get("/user")]
-async fn users(mut db: Connection<Db>) -> CoolerResult<Json<Vec<User>>> {
+async fn users(mut db: Connection<Db>, _admin: AdminUser) -> CoolerResult<Json<Vec<User>>> {
+    // the password hashes are never sent, so they aren't loaded either
     let users = sqlx::query_as!(
         User,
         r#"
-        SELECT id, admin, name, email, password, created_at, updated_at
+        SELECT id, name, email, '' AS "password!", created_at, updated_at,
+            ARRAY(SELECT role FROM user_roles WHERE user_id = users.id ORDER BY role) AS "roles!: Vec<Role>"
         FROM users
         ORDER BY id ASC
         "#
@@ -138,11 +183,201 @@ async fn users(mut db: Connection<Db>) -> CoolerResult<Json<Vec<User>>> {
     Ok(Json(users))
 }
 
+/// What anyone can see of a user.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+struct UserProfile {
+    id: Uuid,
+    name: String,
+    created_at: PrimitiveDateTime,
+    strains: i64,
+    rating: RatingSummary,
+}
+
+/// Ratings of the strains of a user, see [`rating::leaderboard`].
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+struct RatingSummary {
+    /// Strains that have played a rated battle, the rest have no rating yet.
+    rated_strains: i64,
+    best: Option<f64>,
+    average: Option<f64>,
+    games: i64,
+    wins: i64,
+    draws: i64,
+    losses: i64,
+}
+
+#[rocket::get("/user/<user_id_str>")]
+async fn user_profile(
+    mut db: Connection<Db>,
+    user_id_str: String,
+) -> CoolerResult<Option<Json<UserProfile>>> {
+    let Ok(user_id) = Uuid::parse_str(&user_id_str) else {
+        return Ok(None);
+    };
+    let profile = sqlx::query!(
+        r#"
+        SELECT users.id, users.name, users.created_at,
+            COUNT(strains.id) AS "strains!",
+            COUNT(ratings.strain_id) AS "rated_strains!",
+            MAX(ratings.rating) AS best,
+            AVG(ratings.rating) AS average,
+            COALESCE(SUM(ratings.games), 0) AS "games!",
+            COALESCE(SUM(ratings.wins), 0) AS "wins!",
+            COALESCE(SUM(ratings.draws), 0) AS "draws!",
+            COALESCE(SUM(ratings.losses), 0) AS "losses!"
+        FROM users
+        LEFT JOIN strains ON strains.creator_id = users.id
+        LEFT JOIN ratings ON ratings.strain_id = strains.id
+        WHERE users.id = $1
+        GROUP BY users.id
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut **db)
+    .await?
+    .map(|row| UserProfile {
+        id: row.id,
+        name: row.name,
+        created_at: row.created_at,
+        strains: row.strains,
+        rating: RatingSummary {
+            rated_strains: row.rated_strains,
+            best: row.best,
+            average: row.average,
+            games: row.games,
+            wins: row.wins,
+            draws: row.draws,
+            losses: row.losses,
+        },
+    });
+
+    Ok(profile.map(Json))
+}
+
+/// Changes to the account of the logged in user, fields left out stay as they are.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+struct UserUpdate {
+    /// Required for any change, so a stolen session can't take over the account.
+    current_password: String,
+    name: Option<String>,
+    email: Option<String>,
+    password: Option<String>,
+}
+
+#[rocket::patch("/me", data = "<update>")]
+async fn update_me(
+    mut db: Connection<Db>,
+    update: Json<UserUpdate>,
+    auth: AuthUser,
+    role_config: &State<RoleConfig>,
+) -> Result<Json<User>, NewUserError> {
+    let user = auth.user;
+    let current_hash =
+        PasswordHash::new(&user.password).map_err(|_| NewUserError::InternalError)?;
+    Argon2::default()
+        .verify_password(update.current_password.as_bytes(), &current_hash)
+        .map_err(|_| NewUserError::InvalidCredentials)?;
+
+    if update
+        .name
+        .as_ref()
+        .is_some_and(|name| name.trim().is_empty())
+    {
+        return Err(NewUserError::InvalidName);
+    }
+    if update
+        .email
+        .as_ref()
+        .is_some_and(|email| !email.contains('@'))
+    {
+        return Err(NewUserError::InvalidEmail);
+    }
+    // admin emails are trusted to belong to the admins, taking one would make an admin
+    if update
+        .email
+        .as_ref()
+        .is_some_and(|email| email != &user.email && role_config.is_admin_email(email))
+    {
+        return Err(NewUserError::ReservedEmail);
+    }
+    if update
+        .password
+        .as_ref()
+        .is_some_and(|password| password.is_empty())
+    {
+        return Err(NewUserError::InvalidPassword);
+    }
+    let password_hash = update.password.as_deref().map(hash_password).transpose()?;
+
+    let mut tx = sqlx::Connection::begin(&mut **db)
+        .await
+        .map_err(|_| NewUserError::InternalError)?;
+    let row = sqlx::query!(
+        r#"
+        UPDATE users
+        SET name = COALESCE($2, name),
+            email = COALESCE($3, email),
+            password = COALESCE($4, password),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING name, email, updated_at
+        "#,
+        user.id,
+        update.name,
+        update.email,
+        password_hash
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.constraint().is_some() => NewUserError::UserExists,
+        _ => NewUserError::InternalError,
+    })?;
+    if password_hash.is_some() {
+        // whoever knew the old password is logged out everywhere else,
+        // and any token they made with it stops working
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE user_id = $1 AND id <> $2
+            "#,
+            user.id,
+            auth.session_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| NewUserError::InternalError)?;
+        sqlx::query!(
+            r#"
+            DELETE FROM api_tokens
+            WHERE user_id = $1
+            "#,
+            user.id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| NewUserError::InternalError)?;
+    }
+    tx.commit().await.map_err(|_| NewUserError::InternalError)?;
+    println!("user{}| Updated their account", user.id.unwrap());
+
+    Ok(Json(User {
+        name: row.name,
+        email: row.email,
+        updated_at: Some(row.updated_at),
+        ..user
+    }))
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(crate = "rocket::serde")]
 struct Session {
     id: Uuid,
     user_id: Uuid,
+    /// Only given out on login, the database keeps a hash of it.
     token: String,
     created_at: PrimitiveDateTime,
     expires_at: PrimitiveDateTime,
@@ -167,8 +402,6 @@ pub enum LoginError {
     #[error("Internal error")]
     DbErrorCredentialsCheck(sqlx::Error),
     #[error("Internal error")]
-    DbErrorSessionCleanup(sqlx::Error),
-    #[error("Internal error")]
     DbErrorNewSession(sqlx::Error),
     #[error("Internal error")]
     DbError(#[from] sqlx::Error),
@@ -191,6 +424,7 @@ impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for LoginError {
 async fn login(
     mut db: Connection<Db>,
     credentials: Json<Login>,
+    session_config: &State<SessionConfig>,
 ) -> Result<Json<Session>, LoginError> {
     // Get user from db
     let r = sqlx::query!(
@@ -214,40 +448,9 @@ async fn login(
                 .verify_password(credentials.password.as_bytes(), &parsed_hash)
                 .map_err(|_| LoginError::InvalidCredentials)?;
 
-            // delete old sessions
-            sqlx::query!(
-                r#"
-                DELETE FROM sessions
-                WHERE user_id = $1 AND expires_at < NOW()
-                "#,
-                user.id
-            )
-            .execute(&mut **db)
-            .await
-            .map_err(LoginError::DbErrorSessionCleanup)?;
-            // generate token
-            let session_token = uuid::Uuid::new_v4().to_string();
-            // create session in db
-            let row = sqlx::query!(
-                r#"
-                INSERT INTO sessions (user_id, token)
-                VALUES ($1, $2) RETURNING id, created_at, expires_at
-                "#,
-                user.id,
-                session_token
-            )
-            .fetch_one(&mut **db)
-            .await
-            .map_err(LoginError::DbErrorNewSession)?;
-
-            // return session
-            let session = Session {
-                id: row.id,
-                user_id: user.id,
-                token: session_token,
-                created_at: row.created_at,
-                expires_at: row.expires_at,
-            };
+            let session = sessions::create(&mut db, user.id, session_config)
+                .await
+                .map_err(LoginError::DbErrorNewSession)?;
             Ok(Json(session))
         }
         None => Err(LoginError::InvalidCredentials),
@@ -261,9 +464,9 @@ async fn logout(mut db: Connection<Db>, credentials: Json<PublicSession>) -> Coo
         r#"
         DELETE
         FROM sessions
-        WHERE token = $1
+        WHERE token_hash = $1
         "#,
-        credentials.token
+        sessions::hash_token(&credentials.token)
     )
     .execute(&mut **db)
     .await?;
@@ -271,15 +474,8 @@ async fn logout(mut db: Connection<Db>, credentials: Json<PublicSession>) -> Coo
 }
 
 #[rocket::get("/me")]
-async fn validate_session(
-    mut db: Connection<Db>,
-    auth: Credential<OAuth>,
-) -> Result<Json<User>, LoginError> {
-    let user = get_user_from_token(&mut db, &auth.token)
-        .await
-        .ok_or(LoginError::InvalidCredentials)?;
-
-    Ok(Json(user))
+async fn validate_session(auth: AuthUser) -> Json<User> {
+    Json(auth.user)
 }
 
 use serde_with::base64::Base64;
@@ -296,11 +492,16 @@ struct Strain {
     #[serde(skip_serializing_if = "Option::is_none")]
     code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(with = "As::<Option<Base64>>")]
+    #[serde(default, with = "As::<Option<Base64>>")]
     wasm: Option<Vec<u8>>,
     #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     wasm_hash: Option<String>,
     #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    version: Option<i32>,
+    /// Whether the wasm was compiled from the code by the server.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    built: Option<bool>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     created_at: Option<PrimitiveDateTime>,
     #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     updated_at: Option<PrimitiveDateTime>,
@@ -311,12 +512,19 @@ async fn strains(
     mut db: Connection<Db>,
     creator_id: Option<String>,
 ) -> CoolerResult<Json<Vec<Strain>>> {
-    let creator_id = creator_id.map(|s| Uuid::parse_str(&s).ok()).flatten();
+    let creator_id = creator_id.and_then(|s| Uuid::parse_str(&s).ok());
     let strains = sqlx::query_as!(
         Strain,
         r#"
-        SELECT id, name, description, creator_id, NULL AS code, NULL::bytea AS wasm, created_at, updated_at, wasm_hash
+        SELECT strains.id, name, description, creator_id, NULL AS code, NULL::bytea AS wasm, strains.created_at, updated_at, latest.wasm_hash, latest.version, latest.built
         FROM strains
+        INNER JOIN LATERAL (
+            SELECT wasm_hash, version, built
+            FROM strain_versions
+            WHERE strain_versions.strain_id = strains.id
+            ORDER BY version DESC
+            LIMIT 1
+        ) latest ON TRUE
         WHERE creator_id = $1 OR $1 IS NULL
         ORDER BY updated_at DESC
         "#,
@@ -329,43 +537,80 @@ async fn strains(
     Ok(Json(strains))
 }
 
-async fn get_user_from_token(db: &mut Connection<Db>, token: &str) -> Option<User> {
-    let r = sqlx::query_as!(
-        User,
-        r#"
-        SELECT users.id, users.admin, users.name, users.email, users.password, users.created_at, users.updated_at
-        FROM users
-        INNER JOIN sessions ON sessions.user_id = users.id
-        WHERE sessions.token = $1 AND sessions.expires_at > NOW()
-        "#,
-        token
-    )
-    .fetch_optional(&mut ***db)
-    .await
-    .ok()?;
-
-    r
-}
-
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum NewStrainError {
-    #[error("Invalid credentials")]
-    InvalidCredentials,
+    #[error("Strain not found")]
+    NotFound,
+    #[error("Only the creator of a strain can add versions to it")]
+    Forbidden,
     #[error("Constraint violated")]
     ConstraintViolated,
+    #[error("Either wasm or code to build it from is required")]
+    MissingWasm,
+    #[error("{0}")]
+    BuildUnavailable(String),
+    #[error("{0}")]
+    BuildFailed(String),
+    #[error("Invalid strain: {0}")]
+    InvalidWasm(ValidationError),
     #[error("Internal error")]
     InternalError,
 }
+
+/// Body of errors that carry more than a status.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+struct ErrorResponse {
+    error: &'static str,
+    message: String,
+}
+impl From<BuildError> for NewStrainError {
+    fn from(e: BuildError) -> Self {
+        match e {
+            BuildError::Disabled => Self::BuildUnavailable(e.to_string()),
+            BuildError::TooLarge(_) | BuildError::TimedOut | BuildError::Failed(_) => {
+                Self::BuildFailed(e.to_string())
+            }
+            BuildError::Io(e) => {
+                println!("builder| {:?}", e);
+                Self::InternalError
+            }
+        }
+    }
+}
+impl NewStrainError {
+    fn from_db(e: sqlx::Error) -> Self {
+        println!("{:?}", e);
+        match e {
+            sqlx::Error::Database(e) if e.constraint().is_some() => Self::ConstraintViolated,
+            _ => Self::InternalError,
+        }
+    }
+}
 impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for NewStrainError {
     fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
         println!("{:?}", self);
 
         match self {
-            Self::InvalidCredentials => Status::Unauthorized,
-            Self::ConstraintViolated => Status::Conflict,
-            _ => Status::InternalServerError,
+            Self::NotFound => Status::NotFound.respond_to(req),
+            Self::Forbidden => (Status::Forbidden, self.to_string()).respond_to(req),
+            Self::ConstraintViolated => Status::Conflict.respond_to(req),
+            Self::MissingWasm => (Status::BadRequest, self.to_string()).respond_to(req),
+            Self::BuildUnavailable(message) => (Status::NotImplemented, message).respond_to(req),
+            Self::BuildFailed(message) => (Status::UnprocessableEntity, message).respond_to(req),
+            Self::InvalidWasm(e) => {
+                let status = match e {
+                    ValidationError::TooLarge { .. } => Status::PayloadTooLarge,
+                    _ => Status::UnprocessableEntity,
+                };
+                let body = ErrorResponse {
+                    error: e.as_str(),
+                    message: e.to_string(),
+                };
+                (status, Json(body)).respond_to(req)
+            }
+            _ => Status::InternalServerError.respond_to(req),
         }
-        .respond_to(req)
     }
 }
 use sha2::Digest;
@@ -373,53 +618,248 @@ use sha2::Digest;
 async fn new_strain(
     mut db: Connection<Db>,
     mut new_strain: Json<Strain>,
-    auth: Credential<OAuth>,
+    auth: Authorized<roles::UploadStrains>,
+    builder: &State<Builder>,
+    strain_limits: &State<StrainLimits>,
 ) -> Result<Created<Json<Strain>>, NewStrainError> {
-    let user = get_user_from_token(&mut db, &auth.token)
-        .await
-        .ok_or(NewStrainError::InvalidCredentials)?;
+    let user = auth.user;
     new_strain.creator_id = Some(user.id.unwrap());
-    new_strain.wasm_hash = Some(
-        sha2::Sha256::digest(&new_strain.wasm.as_ref().unwrap())
-            .iter()
-            .map(|b| format!("{:02x}", b))
-            .collect::<String>(),
-    );
+    let (wasm, built) = strain_wasm(
+        builder,
+        strain_limits,
+        new_strain.wasm.take(),
+        &new_strain.code,
+    )
+    .await?;
+    new_strain.wasm_hash = Some(wasm_hash(&wasm));
+    new_strain.built = Some(built);
+
+    let mut tx = sqlx::Connection::begin(&mut **db)
+        .await
+        .map_err(|_| NewStrainError::InternalError)?;
     let r = sqlx::query!(
         r#"
-        INSERT INTO strains (name, description, creator_id, code, wasm, wasm_hash)
-        VALUES ($1, $2, $3, $4, $5, $6)
+        INSERT INTO strains (name, description, creator_id)
+        VALUES ($1, $2, $3)
         RETURNING id
         "#,
         new_strain.name,
         new_strain.description,
         new_strain.creator_id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(NewStrainError::from_db)?;
+    sqlx::query!(
+        r#"
+        INSERT INTO strain_versions (strain_id, version, code, wasm, wasm_hash, built)
+        VALUES ($1, 1, $2, $3, $4, $5)
+        "#,
+        r.id,
         new_strain.code,
-        new_strain.wasm,
-        new_strain.wasm_hash
+        wasm,
+        new_strain.wasm_hash,
+        built
     )
-    .fetch_optional(&mut **db)
-    .await;
+    .execute(&mut *tx)
+    .await
+    .map_err(NewStrainError::from_db)?;
+    tx.commit()
+        .await
+        .map_err(|_| NewStrainError::InternalError)?;
 
-    match r {
-        Ok(Some(row)) => {
-            // Set the new user's id from the returned value and return as before
-            new_strain.id = Some(row.id);
-            // clear code and wasm to save bandwidth
-            new_strain.code = None;
-            new_strain.wasm = None;
-            return Ok(Created::new("/strain").body(new_strain));
-        }
-        Err(sqlx::Error::Database(e)) => {
-            println!("{:?}", e);
-            if e.constraint().is_some() {
-                // The constraint was violated, so we know the user already exists
-                return Err(NewStrainError::ConstraintViolated);
-            }
-        }
-        _ => {}
+    new_strain.id = Some(r.id);
+    new_strain.version = Some(1);
+    // clear code to save bandwidth
+    new_strain.code = None;
+    Ok(Created::new("/strain").body(new_strain))
+}
+
+/// The uploaded wasm, or if there is none the code compiled by the server, and whether it was
+/// compiled. Either way it has to pass [`validate_strain`].
+async fn strain_wasm(
+    builder: &Builder,
+    strain_limits: &StrainLimits,
+    wasm: Option<Vec<u8>>,
+    code: &Option<String>,
+) -> Result<(Vec<u8>, bool), NewStrainError> {
+    let (wasm, built) = match (wasm, code) {
+        (Some(wasm), _) => (wasm, false),
+        (None, Some(code)) => (builder.build(code).await?, true),
+        (None, None) => return Err(NewStrainError::MissingWasm),
+    };
+    let limits = ResourceLimits::from(*strain_limits);
+    // the smoke test runs the strain
+    rocket::tokio::task::spawn_blocking(move || {
+        validate_strain(&wasm, &limits).map(|()| (wasm, built))
+    })
+    .await
+    .map_err(|_| NewStrainError::InternalError)?
+    .map_err(NewStrainError::InvalidWasm)
+}
+
+fn wasm_hash(wasm: &[u8]) -> String {
+    sha2::Sha256::digest(wasm)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+struct StrainVersion {
+    #[serde(skip_deserializing)]
+    id: Uuid,
+    #[serde(skip_deserializing)]
+    strain_id: Uuid,
+    #[serde(skip_deserializing)]
+    version: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, with = "As::<Option<Base64>>")]
+    wasm: Option<Vec<u8>>,
+    #[serde(skip_deserializing)]
+    wasm_hash: String,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    wasm_size: Option<i32>,
+    /// Whether the wasm was compiled from the code by the server.
+    #[serde(skip_deserializing)]
+    built: bool,
+    #[serde(skip_deserializing)]
+    created_at: Option<PrimitiveDateTime>,
+}
+
+#[rocket::post("/strain/<strain_id_str>/versions", data = "<new_version>")]
+async fn new_strain_version(
+    mut db: Connection<Db>,
+    strain_id_str: String,
+    mut new_version: Json<StrainVersion>,
+    auth: Authorized<roles::UploadStrains>,
+    builder: &State<Builder>,
+    strain_limits: &State<StrainLimits>,
+) -> Result<Created<Json<StrainVersion>>, NewStrainError> {
+    let strain_id = Uuid::parse_str(&strain_id_str).map_err(|_| NewStrainError::NotFound)?;
+    let user = auth.user;
+    let strain = sqlx::query!(
+        r#"
+        SELECT creator_id
+        FROM strains
+        WHERE id = $1
+        "#,
+        strain_id
+    )
+    .fetch_optional(&mut **db)
+    .await
+    .map_err(|_| NewStrainError::InternalError)?
+    .ok_or(NewStrainError::NotFound)?;
+    if user.id.unwrap() != strain.creator_id {
+        return Err(NewStrainError::Forbidden);
     }
-    Err(NewStrainError::InternalError)
+    let (wasm, built) = strain_wasm(
+        builder,
+        strain_limits,
+        new_version.wasm.take(),
+        &new_version.code,
+    )
+    .await?;
+    new_version.wasm_hash = wasm_hash(&wasm);
+    new_version.built = built;
+
+    let mut tx = sqlx::Connection::begin(&mut **db)
+        .await
+        .map_err(|_| NewStrainError::InternalError)?;
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO strain_versions (strain_id, version, code, wasm, wasm_hash, built)
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4, $5
+        FROM strain_versions
+        WHERE strain_id = $1
+        RETURNING id, version, octet_length(wasm) AS wasm_size, created_at
+        "#,
+        strain_id,
+        new_version.code,
+        wasm,
+        new_version.wasm_hash,
+        built
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(NewStrainError::from_db)?;
+    sqlx::query!(
+        r#"
+        UPDATE strains
+        SET updated_at = NOW()
+        WHERE id = $1
+        "#,
+        strain_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(NewStrainError::from_db)?;
+    tx.commit()
+        .await
+        .map_err(|_| NewStrainError::InternalError)?;
+
+    new_version.id = row.id;
+    new_version.strain_id = strain_id;
+    new_version.version = row.version;
+    new_version.wasm_size = row.wasm_size;
+    new_version.created_at = Some(row.created_at);
+    // clear code to save bandwidth
+    new_version.code = None;
+    Ok(Created::new(format!("/strain/{strain_id}/versions/{}", row.version)).body(new_version))
+}
+
+#[rocket::get("/strain/<strain_id_str>/versions")]
+async fn strain_versions(
+    mut db: Connection<Db>,
+    strain_id_str: String,
+) -> CoolerResult<Option<Json<Vec<StrainVersion>>>> {
+    let Ok(strain_id) = Uuid::parse_str(&strain_id_str) else {
+        return Ok(None);
+    };
+    let versions = sqlx::query_as!(
+        StrainVersion,
+        r#"
+        SELECT id, strain_id, version, NULL AS code, NULL::bytea AS wasm, wasm_hash, octet_length(wasm) AS wasm_size, built, created_at
+        FROM strain_versions
+        WHERE strain_id = $1
+        ORDER BY version DESC
+        "#,
+        strain_id
+    )
+    .fetch(&mut **db)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    // every strain has at least one version
+    Ok((!versions.is_empty()).then_some(Json(versions)))
+}
+
+#[rocket::get("/strain/<strain_id_str>/versions/<version>")]
+async fn strain_version(
+    mut db: Connection<Db>,
+    strain_id_str: String,
+    version: i32,
+) -> CoolerResult<Option<Json<StrainVersion>>> {
+    let Ok(strain_id) = Uuid::parse_str(&strain_id_str) else {
+        return Ok(None);
+    };
+    let version = sqlx::query_as!(
+        StrainVersion,
+        r#"
+        SELECT id, strain_id, version, code, NULL::bytea AS wasm, wasm_hash, octet_length(wasm) AS wasm_size, built, created_at
+        FROM strain_versions
+        WHERE strain_id = $1 AND version = $2
+        "#,
+        strain_id,
+        version
+    )
+    .fetch_optional(&mut **db)
+    .await?;
+
+    Ok(version.map(Json))
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
@@ -431,6 +871,9 @@ struct StrainWithoutWasm {
     code: String,
     wasm_hash: String,
     wasm_size: Option<i32>,
+    version: i32,
+    /// Whether the wasm was compiled from the code by the server.
+    built: bool,
     creator_id: Uuid,
     created_at: PrimitiveDateTime,
     updated_at: PrimitiveDateTime,
@@ -440,34 +883,41 @@ struct StrainWithoutWasm {
 async fn strain(
     mut db: Connection<Db>,
     strain_id_str: String,
-) -> CoolerResult<Json<StrainWithoutWasm>> {
-    let strain_id = Uuid::parse_str(&strain_id_str.to_string()).expect("Invalid strain id");
+) -> CoolerResult<Option<Json<StrainWithoutWasm>>> {
+    let Ok(strain_id) = Uuid::parse_str(&strain_id_str) else {
+        return Ok(None);
+    };
     let strain = sqlx::query_as!(
         StrainWithoutWasm,
         r#"
-        SELECT id, name, creator_id, created_at, updated_at, description, code, octet_length(wasm) AS wasm_size, wasm_hash
+        SELECT strains.id, name, creator_id, strains.created_at, updated_at, description, latest.code, octet_length(latest.wasm) AS wasm_size, latest.wasm_hash, latest.version, latest.built
         FROM strains
-        WHERE id = $1
+        INNER JOIN LATERAL (
+            SELECT code, wasm, wasm_hash, version, built
+            FROM strain_versions
+            WHERE strain_versions.strain_id = strains.id
+            ORDER BY version DESC
+            LIMIT 1
+        ) latest ON TRUE
+        WHERE strains.id = $1
         "#,
         strain_id
     )
-    .fetch_one(&mut **db)
+    .fetch_optional(&mut **db)
     .await?;
 
-    Ok(Json(strain))
+    Ok(strain.map(Json))
 }
 
 #[rocket::delete("/strain/<strain_id_str>")]
 async fn delete_strain(
     mut db: Connection<Db>,
     strain_id_str: String,
-    auth: Credential<OAuth>,
+    auth: AuthUser,
 ) -> Result<(), LoginError> {
     let strain_id =
         Uuid::parse_str(&strain_id_str.to_string()).map_err(|_| LoginError::InvalidCredentials)?;
-    let user = get_user_from_token(&mut db, &auth.token)
-        .await
-        .ok_or(LoginError::InvalidCredentials)?;
+    let user = auth.user;
     let strain = sqlx::query!(
         r#"
         SELECT creator_id
@@ -478,7 +928,7 @@ async fn delete_strain(
     )
     .fetch_one(&mut **db)
     .await?;
-    if user.id.unwrap() != strain.creator_id {
+    if user.id.unwrap() != strain.creator_id && !user.can(Permission::ModerateStrains) {
         return Err(LoginError::InvalidCredentials);
     }
     sqlx::query!(
@@ -499,19 +949,31 @@ async fn delete_strain(
 struct BattleRequest {
     strain_a: Uuid,
     strain_b: Uuid,
+    /// Defaults to the standard arena, as do any of its fields left out.
+    #[serde(default)]
+    arena: Arena,
+    /// Defaults to a random seed, give one to replay a match.
+    seed: Option<i64>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(crate = "rocket::serde")]
 struct BattleResult {
     id: Uuid,
-    arena_size: i32,
+    status: BattleStatus,
+    arena_id: Uuid,
+    arena: Arena,
+    seed: Option<i64>,
     strain_a: Uuid,
     strain_b: Uuid,
+    version_a: Uuid,
+    version_b: Uuid,
     winner: Option<Uuid>,
     score_a: i32,
     score_b: i32,
+    forfeit: Option<bool>,
     log: Vec<BattleLog>,
+    violations: Vec<BattleViolation>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
@@ -521,334 +983,316 @@ struct BattleLog {
     x: i32,
     y: i32,
     allowed: bool,
+    /// Microseconds the strain took to pick the move.
+    duration_us: Option<i64>,
+    /// Number of moves the strain could choose from.
+    allowed_count: Option<i32>,
+    /// Fuel the strain used for the move.
+    fuel: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+struct BattleViolation {
+    turn: i32,
+    player: bool,
+    reason: String,
+    message: Option<String>,
+}
+
+impl From<&MoveLog> for BattleLog {
+    fn from(entry: &MoveLog) -> Self {
+        Self {
+            player: entry.player.as_bool(),
+            x: entry.x,
+            y: entry.y,
+            allowed: true,
+            duration_us: Some(entry.duration.as_micros() as i64),
+            allowed_count: Some(entry.allowed as i32),
+            fuel: entry.fuel.map(|fuel| fuel as i64),
+        }
+    }
+}
+
+impl From<&Violation> for BattleViolation {
+    fn from(violation: &Violation) -> Self {
+        Self {
+            turn: violation.turn as i32,
+            player: violation.player.as_bool(),
+            reason: violation.reason.as_str().to_string(),
+            // postgres does not accept NUL in text columns
+            message: violation.reason.details().map(|m| m.replace('\0', "")),
+        }
+    }
+}
+
+/// Resource limits for strain plugins, configurable through the rocket config
+/// (e.g. `ROCKET_STRAIN_CALL_TIMEOUT_MS`).
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+struct StrainLimits {
+    strain_call_fuel: u64,
+    strain_match_fuel: u64,
+    strain_call_timeout_ms: u64,
+    strain_match_budget_ms: u64,
+    strain_memory_max_pages: u32,
+    strain_max_wasm_bytes: usize,
+}
+
+impl Default for StrainLimits {
+    fn default() -> Self {
+        let limits = ResourceLimits::default();
+        Self {
+            strain_call_fuel: limits.call_fuel,
+            strain_match_fuel: limits.match_fuel,
+            strain_call_timeout_ms: limits.call_timeout.as_millis() as u64,
+            strain_match_budget_ms: limits.match_budget.as_millis() as u64,
+            strain_memory_max_pages: limits.memory_max_pages,
+            strain_max_wasm_bytes: limits.max_wasm_bytes,
+        }
+    }
+}
+
+impl From<StrainLimits> for ResourceLimits {
+    fn from(value: StrainLimits) -> Self {
+        Self {
+            call_fuel: value.strain_call_fuel,
+            match_fuel: value.strain_match_fuel,
+            call_timeout: Duration::from_millis(value.strain_call_timeout_ms),
+            match_budget: Duration::from_millis(value.strain_match_budget_ms),
+            memory_max_pages: value.strain_memory_max_pages,
+            max_wasm_bytes: value.strain_max_wasm_bytes,
+        }
+    }
 }
 
 async fn get_latest_strain_version(
-    db: &mut Connection<Db>,
+    db: &mut sqlx::PgConnection,
     strain_id: Uuid,
-) -> CoolerResult<Strain> {
-    let strain = sqlx::query_as!(
-        Strain,
+) -> sqlx::Result<StrainVersion> {
+    let version = sqlx::query_as!(
+        StrainVersion,
         r#"
-        SELECT id, name, description, creator_id, code, wasm, created_at, updated_at, wasm_hash
-        FROM strains
-        WHERE id = $1
+        SELECT id, strain_id, version, code, wasm, wasm_hash, octet_length(wasm) AS wasm_size, built, created_at
+        FROM strain_versions
+        WHERE strain_id = $1
+        ORDER BY version DESC
+        LIMIT 1
         "#,
         strain_id
     )
-    .fetch_one(&mut ***db)
+    .fetch_one(db)
     .await?;
 
-    Ok(strain)
+    Ok(version)
 }
 
-async fn process_turn(
-    battle_id: Uuid,
-    board: &Vec<((i32, i32), Option<bool>)>,
-    plugin: &mut Plugin,
-) -> Option<(i32, i32)> {
-    let empty = board
-        .iter()
-        .filter(|(_, v)| v.is_none())
-        .map(|(k, _)| *k)
-        .collect::<Vec<_>>();
-    let occupied = board
-        .iter()
-        .filter(|(_, v)| v.is_some())
-        .map(|(k, v)| (*k, *v))
-        .collect::<Vec<_>>();
-    let friendly = occupied
-        .iter()
-        .filter(|(_, v)| *v == Some(true))
-        .map(|(k, _)| *k)
-        .collect::<Vec<_>>();
-    let _enemy = occupied
-        .iter()
-        .filter(|(_, v)| *v == Some(false))
-        .map(|(k, _)| *k)
-        .collect::<Vec<_>>();
-    // Only allow moves that are directly adjacent to an occupied square (with diagonals) and are the same color
-    let allowed = empty
-        .iter()
-        .filter(|(x, y)| {
-            friendly.iter().any(|(ox, oy)| {
-                (x == ox && (y - oy).abs() == 1) || (y == oy && (x - ox).abs() == 1)
-            })
-        })
-        .map(|(x, y)| (*x, *y))
-        .collect::<Vec<_>>();
-    /*
-    println!(
-        "battle{battle_id}| Turn: {}, Allowed moves: {:?}, Occupied: {:?}",
-        if turn { "A" } else { "B" },
-        allowed,
-        occupied
-    ); */
-    if allowed.len() == 0 {
-        return None;
+async fn get_strain_version(
+    db: &mut sqlx::PgConnection,
+    version_id: Uuid,
+) -> sqlx::Result<StrainVersion> {
+    let version = sqlx::query_as!(
+        StrainVersion,
+        r#"
+        SELECT id, strain_id, version, code, wasm, wasm_hash, octet_length(wasm) AS wasm_size, built, created_at
+        FROM strain_versions
+        WHERE id = $1
+        "#,
+        version_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(version)
+}
+
+#[derive(Error, Debug)]
+pub enum BattleError {
+    #[error("Invalid arena: {0}")]
+    InvalidArena(String),
+    #[error("Battle not found")]
+    NotFound,
+    #[error("Battle has not been played yet")]
+    NotFinished,
+    #[error("Battle was played without a seed")]
+    NoSeed,
+    #[error("Internal error")]
+    DbError(#[from] sqlx::Error),
+}
+impl BattleError {
+    /// Like the `From` conversion, but a missing row means something asked for doesn't exist.
+    fn from_db(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            e => Self::DbError(e),
+        }
     }
+}
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for BattleError {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        println!("{:?}", self);
 
-    let input = StrainInput {
-        board: board.clone(),
-        allowed: allowed.clone(),
-    };
-    let extism::convert::Json(response) = plugin
-        .call::<extism::convert::Json<StrainInput>, extism::convert::Json<StrainOutput>>(
-            "take_turn",
-            extism::convert::Json(input),
-        )
-        .expect("Failed to call plugin");
-    if !allowed.contains(&response) {
-        println!("battle{battle_id}| Invalid move: {:?}", response);
-        return None;
+        match self {
+            Self::InvalidArena(message) => (Status::BadRequest, message).respond_to(req),
+            Self::NotFound => Status::NotFound.respond_to(req),
+            Self::NotFinished | Self::NoSeed => {
+                (Status::Conflict, self.to_string()).respond_to(req)
+            }
+            _ => Status::InternalServerError.respond_to(req),
+        }
     }
-    Some(response)
 }
 
 #[rocket::post("/battle", data = "<battle_request>")]
 async fn battle(
     mut db: Connection<Db>,
     battle_request: Json<BattleRequest>,
-    auth: Credential<OAuth>,
-) -> CoolerResult<Json<BattleResult>> {
-    const BOARD_SIZE: usize = 24;
-    let user = get_user_from_token(&mut db, &auth.token)
+    auth: Authorized<roles::RunBattles>,
+    arena_bounds: &State<ArenaBounds>,
+) -> Result<Accepted<Json<BattleJob>>, BattleError> {
+    let user = auth.user;
+    arena_bounds
+        .check(&battle_request.arena)
+        .map_err(BattleError::InvalidArena)?;
+    let strain_a = get_latest_strain_version(&mut db, battle_request.strain_a)
         .await
-        .ok_or(LoginError::InvalidCredentials)
-        .expect("Invalid credentials");
-    if !user.admin.unwrap() {
-        panic!("User is not admin");
-    }
-    let battle_id = Uuid::new_v4();
+        .map_err(BattleError::from_db)?;
+    let strain_b = get_latest_strain_version(&mut db, battle_request.strain_b)
+        .await
+        .map_err(BattleError::from_db)?;
+
+    let arena_id = arena::arena_id(&mut db, &battle_request.arena).await?;
+    let seed = battle_request.seed.unwrap_or_else(queue::new_seed);
+    let job = queue::enqueue(&mut db, arena_id, strain_a.id, strain_b.id, seed, user.id).await?;
     println!(
-        "battle{battle_id}| user {} requested a battle between {} and {}",
+        "battle{}| user {} queued a battle between {} and {}",
+        job.id,
         user.id.unwrap(),
         battle_request.strain_a,
         battle_request.strain_b
     );
-    // check for existing result
-    let row = sqlx::query!(
-        r#"
-        SELECT id, arena_size, strain_a, strain_b, winner, score_a, score_b
-        FROM battles
-        WHERE strain_a = $1 AND strain_b = $2
-        "#,
-        battle_request.strain_a,
-        battle_request.strain_b
-    )
-    .fetch_optional(&mut **db)
-    .await?;
-    if let Some(old) = row {
-        println!("battle{battle_id}| Battle already exists, deleting the old one");
-        sqlx::query!(
-            r#"
-            DELETE FROM battles
-            WHERE id = $1
-            "#,
-            old.id
-        )
-        .execute(&mut **db)
-        .await?;
-        sqlx::query!(
-            r#"
-            DELETE FROM battle_logs
-            WHERE battle_id = $1
-            "#,
-            old.id
-        )
-        .execute(&mut **db)
-        .await?;
-    }
-
-    /*
-    if let Some(row) = row {
-        println!("battle{battle_id}| Battle already exists");
-        let log = sqlx::query_as!(
-            BattleLog,
-            r#"
-            SELECT move_x AS x, move_y AS y, last, allowed
-            FROM battle_logs
-            WHERE battle_id = $1
-            ORDER BY turn ASC
-            "#,
-            battle_id
-        )
-        .fetch(&mut **db)
-        .try_collect::<Vec<_>>()
-        .await?;
-        return Ok(Json(BattleResult {
-            id: row.id,
-            arena_size: row.arena_size,
-            strain_a: row.strain_a,
-            strain_b: row.strain_b,
-            winner: row.winner,
-            score_a: row.score_a,
-            score_b: row.score_b,
-            log,
-        }));
-    } */
-    println!("battle{battle_id}| Loading strain versions");
-    let strain_a = get_latest_strain_version(&mut db, battle_request.strain_a).await?;
-    let strain_b = get_latest_strain_version(&mut db, battle_request.strain_b).await?;
+    Ok(Accepted(Json(job)))
+}
 
-    println!("battle{battle_id}| Loading wasm");
+/// Plays a match, blocking until it is over.
+/// The same strains on the same arena with the same seed always play out the same way, unless the
+/// host is so slow that one of them runs into the wall-clock backstops of its limits.
+fn play(
+    arena: Arena,
+    seed: Seed,
+    wasm_a: Vec<u8>,
+    wasm_b: Vec<u8>,
+    limits: &ResourceLimits,
+    on_move: impl FnMut(&GameState, MoveLog),
+) -> MatchResult {
+    // a strain that fails to load forfeits instead of failing the whole battle
+    let plugin_a = PluginProvider::load(wasm_a, limits, strain_seed(seed, Player::A));
+    let plugin_b = PluginProvider::load(wasm_b, limits, strain_seed(seed, Player::B));
+    let state = GameState::with_arena(arena).expect("Arena was validated when requested");
+    Match::new(state, plugin_a, plugin_b).run_with(on_move)
+}
 
-    let wasm_a = strain_a.wasm.expect("No wasm for strain a");
-    let wasm_b = strain_b.wasm.expect("No wasm for strain b");
-
-    let url = Wasm::data(wasm_a);
-    let manifest = Manifest::new([url]);
-    let mut plugin_a = Plugin::new(&manifest, [], true).expect("Failed to load plugin");
-
-    let url = Wasm::data(wasm_b);
-    let manifest = Manifest::new([url]);
-    let mut plugin_b = Plugin::new(&manifest, [], true).expect("Failed to load plugin");
-
-    println!("battle{battle_id}| Plugins loaded!");
-
-    // None = empty
-    // Some(true) = player a
-    // Some(false) = player b
-    let mut board = vec![];
-    for y in 0..BOARD_SIZE {
-        for x in 0..BOARD_SIZE {
-            let value = if x == 0 && y == 0 {
-                Some(true)
-            } else if x == BOARD_SIZE - 1 && y == BOARD_SIZE - 1 {
-                Some(false)
-            } else {
-                None
-            };
-            board.push(((x as i32, y as i32), value));
-        }
+/// Plays a battle between two strain versions and stores the result, finishing its job.
+/// Earlier battles of the same pairing keep their results, tournaments may link to them.
+/// Plays the battle of a claimed job and stores its result.
+/// No connection is held while the match is played, the pool is shared with the lease heartbeats and
+/// the requests.
+async fn run_battle(
+    pool: &sqlx::PgPool,
+    job: &BattleJob,
+    mut strain_a: StrainVersion,
+    mut strain_b: StrainVersion,
+    limits: &ResourceLimits,
+    streams: &BattleStreams,
+) -> sqlx::Result<BattleResult> {
+    let battle_id = job.id;
+    // results are stored together with the job status, so a stored result is complete.
+    // A job can still be retried after a crash between the two, or when its lease ran out.
+    let mut db = pool.acquire().await?;
+    if battle_exists(&mut db, battle_id).await? {
+        println!("battle{battle_id}| Already played, keeping the stored result");
+        queue::complete(&mut db, battle_id).await?;
+        let result = load_battle(&mut db, job).await?;
+        return Ok(BattleResult {
+            status: BattleStatus::Finished,
+            ..result
+        });
     }
+    drop(db);
 
-    let mut log = vec![];
-
-    // true = player a
-    // false = player b
-    let mut turn = false;
-    let mut skips = 0;
-    const MOVES_PER_TURN: usize = 5;
-    'outer: loop {
-        turn = !turn;
-        for _ in 0..MOVES_PER_TURN {
-            // rotate the board so that the active player is always at the top left
-            // this way the plugin doesn't have to worry about the board rotation
-            // also change the mappings so that friendly cells are always true and enemy cells are always false
-            let board_copy = if turn {
-                board.clone()
-            } else {
-                board
-                    .iter()
-                    .map(|((x, y), v)| {
-                        (
-                            ((BOARD_SIZE as i32 - 1) - y, (BOARD_SIZE as i32 - 1) - x),
-                            v.map(|v| if turn { v } else { !v }),
-                        )
-                    })
-                    .collect::<Vec<_>>()
-            };
-            // (0, 0) should always be true
-            if !board_copy
-                .iter()
-                .any(|((x, y), v)| *x == 0 && *y == 0 && *v == Some(true))
-            {
-                // something extremely weird happened
-                println!(
-                    "battle{battle_id}| Something weird happened ((0,0) != true), ending game"
-                );
-                println!(
-                    "battle{battle_id}| Board: {:?}",
-                    board_copy
-                        .iter()
-                        .filter(|(_, v)| v.is_some())
-                        .map(|((x, y), v)| (*x, *y, v.unwrap()))
-                        .collect::<Vec<_>>()
-                );
-                break;
-            }
+    println!("battle{battle_id}| Loading wasm");
 
-            let active_player = if turn { &mut plugin_a } else { &mut plugin_b };
-            let response = process_turn(battle_id, &board_copy, active_player).await;
-
-            if let Some(response) = response {
-                // rotate the response so that it matches the original board
-                let corrected_response: StrainOutput = if !turn {
-                    (
-                        (BOARD_SIZE as i32 - 1) - response.1,
-                        (BOARD_SIZE as i32 - 1) - response.0,
-                    )
-                } else {
-                    response
-                };
-                println!(
-                    "battle{battle_id}| Player {} made the move {:?} -> {:?}",
-                    if turn { "A" } else { "B" },
-                    response,
-                    corrected_response
-                );
-                log.push((corrected_response, turn, true));
-                board
-                    .iter_mut()
-                    .filter(|(k, _)| *k == corrected_response)
-                    .for_each(|(_, v)| *v = Some(turn));
-                skips = 0;
-            } else {
-                println!(
-                    "battle{battle_id}| No moves available, skipping turn ({} skips in total)",
-                    skips
-                );
-                skips += 1;
-                if skips >= 2 * MOVES_PER_TURN {
-                    println!("battle{battle_id}| Both players skipped, ending game");
-                    break 'outer;
-                }
-                continue;
-            }
-        }
+    let wasm_a = strain_a.wasm.take().expect("No wasm for strain a");
+    let wasm_b = strain_b.wasm.take().expect("No wasm for strain b");
+
+    // plugins block while they run, keep them off the async workers
+    let limits = *limits;
+    let arena = job.arena.0;
+    // jobs queued before seeds existed got one in the migration, this is just in case
+    let seed = job.seed.unwrap_or_default() as Seed;
+    let streams = streams.clone();
+    let result = rocket::tokio::task::spawn_blocking(move || {
+        let mut turn = 0;
+        play(arena, seed, wasm_a, wasm_b, &limits, |state, entry| {
+            streams.publish(battle_id, BattleEvent::from_move(turn, state, entry));
+            turn += 1;
+        })
+    })
+    .await
+    .expect("Battle panicked");
+    println!(
+        "battle{battle_id}| Battle finished after {} moves, score {} - {}",
+        result.log.len(),
+        result.score_a,
+        result.score_b
+    );
+    for violation in &result.violations {
+        println!(
+            "battle{battle_id}| Player {:?} failed at move {}: {}",
+            violation.player, violation.turn, violation.reason
+        );
     }
 
-    let score_a = board.iter().filter(|(_, v)| *v == Some(true)).count() as i32;
-    let score_b = board.iter().filter(|(_, v)| *v == Some(false)).count() as i32;
-    let winner = if score_a > score_b {
-        Some(strain_a.id.unwrap())
-    } else if score_b > score_a {
-        Some(strain_b.id.unwrap())
-    } else {
-        None
-    };
+    let score_a = result.score_a;
+    let score_b = result.score_b;
+    let winner = result.winner.map(|winner| match winner {
+        Player::A => strain_a.strain_id,
+        Player::B => strain_b.strain_id,
+    });
 
     // save to db
-    let log = log
+    let log = result.log.iter().map(BattleLog::from).collect::<Vec<_>>();
+    let violations = result
+        .violations
         .iter()
-        .map(|((x, y), player, allowed)| BattleLog {
-            player: *player,
-            x: *x as i32,
-            y: *y as i32,
-            allowed: *allowed,
-        })
+        .map(BattleViolation::from)
         .collect::<Vec<_>>();
+    let forfeit = result.forfeit.map(Player::as_bool);
+    // the result, the ratings and the job status are stored all or nothing
+    let mut tx = pool.begin().await?;
     sqlx::query!(
         r#"
-        INSERT INTO battles (id, arena_size, strain_a, strain_b, winner, score_a, score_b)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO battles (id, arena_id, strain_a, strain_b, version_a, version_b, winner, score_a, score_b, forfeit)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         "#,
         battle_id,
-        BOARD_SIZE as i32,
+        job.arena_id,
+        strain_a.strain_id,
+        strain_b.strain_id,
         strain_a.id,
         strain_b.id,
         winner,
         score_a,
         score_b,
+        forfeit,
     )
-    .execute(&mut **db)
+    .execute(&mut *tx)
     .await?;
     for (turn, log_entry) in log.iter().enumerate() {
         sqlx::query!(
             r#"
-            INSERT INTO battle_logs (battle_id, turn, move_x, move_y, player, allowed)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO battle_logs (battle_id, turn, move_x, move_y, player, allowed, duration_us, allowed_count, fuel)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             "#,
             battle_id,
             turn as i32,
@@ -856,72 +1300,157 @@ async fn battle(
             log_entry.y,
             log_entry.player,
             log_entry.allowed,
+            log_entry.duration_us,
+            log_entry.allowed_count,
+            log_entry.fuel,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    for (seq, violation) in violations.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO battle_violations (battle_id, seq, turn, player, reason, message)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            battle_id,
+            seq as i32,
+            violation.turn,
+            violation.player,
+            violation.reason,
+            violation.message,
         )
-        .execute(&mut **db)
+        .execute(&mut *tx)
         .await?;
     }
+    rating::record_battle(&mut tx, battle_id, &strain_a, &strain_b, result.winner).await?;
+    queue::complete(&mut tx, battle_id).await?;
+    tx.commit().await?;
 
-    Ok(Json(BattleResult {
+    Ok(BattleResult {
         id: battle_id,
-        arena_size: BOARD_SIZE as i32,
-        strain_a: strain_a.id.unwrap(),
-        strain_b: strain_b.id.unwrap(),
+        status: BattleStatus::Finished,
+        arena_id: job.arena_id,
+        arena: job.arena.0,
+        seed: job.seed,
+        strain_a: strain_a.strain_id,
+        strain_b: strain_b.strain_id,
+        version_a: strain_a.id,
+        version_b: strain_b.id,
         winner,
         score_a,
         score_b,
+        forfeit,
         log,
-    }))
+        violations,
+    })
+}
+
+async fn battle_exists(db: &mut sqlx::PgConnection, battle_id: Uuid) -> sqlx::Result<bool> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM battles WHERE id = $1) AS "exists!"
+        "#,
+        battle_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row.exists)
+}
+
+/// A finished battle, or the job of one that has not been played yet.
+#[derive(rocket::Responder)]
+enum BattleResponse {
+    Result(Json<BattleResult>),
+    Job(Json<BattleJob>),
 }
 
 #[rocket::get("/battle/<battle_id_str>")]
 async fn get_battle(
     mut db: Connection<Db>,
     battle_id_str: String,
-) -> CoolerResult<Json<BattleResult>> {
-    let battle_id = Uuid::parse_str(&battle_id_str.to_string()).expect("Invalid battle id");
+) -> CoolerResult<Option<BattleResponse>> {
+    let Ok(battle_id) = Uuid::parse_str(&battle_id_str) else {
+        return Ok(None);
+    };
+    let Some(job) = queue::get_job(&mut db, battle_id).await? else {
+        return Ok(None);
+    };
+    if job.status != BattleStatus::Finished {
+        return Ok(Some(BattleResponse::Job(Json(job))));
+    }
+    let result = load_battle(&mut db, &job).await?;
+    Ok(Some(BattleResponse::Result(Json(result))))
+}
+
+/// Loads the stored result of a finished battle job.
+async fn load_battle(db: &mut sqlx::PgConnection, job: &BattleJob) -> sqlx::Result<BattleResult> {
     let row = sqlx::query!(
         r#"
-        SELECT id, arena_size, strain_a, strain_b, winner, score_a, score_b
+        SELECT id, strain_a, strain_b, version_a, version_b, winner, score_a, score_b, forfeit
         FROM battles
         WHERE id = $1
         "#,
-        battle_id
+        job.id
     )
-    .fetch_one(&mut **db)
+    .fetch_one(&mut *db)
     .await?;
     let log = sqlx::query_as!(
         BattleLog,
         r#"
-        SELECT move_x AS x, move_y AS y, allowed, player
+        SELECT move_x AS x, move_y AS y, allowed, player, duration_us, allowed_count, fuel
         FROM battle_logs
         WHERE battle_id = $1
         ORDER BY turn ASC
         "#,
-        battle_id
+        job.id
     )
-    .fetch(&mut **db)
+    .fetch(&mut *db)
+    .try_collect::<Vec<_>>()
+    .await?;
+    let violations = sqlx::query_as!(
+        BattleViolation,
+        r#"
+        SELECT turn, player, reason, message
+        FROM battle_violations
+        WHERE battle_id = $1
+        ORDER BY seq ASC
+        "#,
+        job.id
+    )
+    .fetch(&mut *db)
     .try_collect::<Vec<_>>()
     .await?;
 
-    Ok(Json(BattleResult {
+    Ok(BattleResult {
         id: row.id,
-        arena_size: row.arena_size,
+        status: job.status,
+        arena_id: job.arena_id,
+        arena: job.arena.0,
+        seed: job.seed,
         strain_a: row.strain_a,
         strain_b: row.strain_b,
+        version_a: row.version_a,
+        version_b: row.version_b,
         winner: row.winner,
         score_a: row.score_a,
         score_b: row.score_b,
+        forfeit: row.forfeit,
         log,
-    }))
+        violations,
+    })
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(crate = "rocket::serde")]
 struct BattleIndex {
     id: Uuid,
-    arena_size: i32,
+    arena_id: Uuid,
+    arena: sqlx::types::Json<Arena>,
     strain_a: Uuid,
     strain_b: Uuid,
+    version_a: Uuid,
+    version_b: Uuid,
     winner: Option<Uuid>,
     score_a: i32,
     score_b: i32,
@@ -932,9 +1461,10 @@ async fn battles(mut db: Connection<Db>) -> CoolerResult<Json<Vec<BattleIndex>>>
     let battles = sqlx::query_as!(
         BattleIndex,
         r#"
-        SELECT battles.id, arena_size, strain_a, strain_b, winner, score_a, score_b
+        SELECT battles.id, arena_id, config AS "arena: sqlx::types::Json<Arena>", strain_a, strain_b, version_a, version_b, winner, score_a, score_b
         FROM battles
-        ORDER BY created_at DESC
+        INNER JOIN arenas ON arenas.id = battles.arena_id
+        ORDER BY battles.created_at DESC
         "#
     )
     .fetch(&mut **db)
@@ -966,21 +1496,53 @@ async fn rocket() -> _ {
         .attach(cors)
         .attach(Db::init())
         .attach(AdHoc::try_on_ignite("SQLx Migrations", run_migrations))
+        .attach(AdHoc::config::<StrainLimits>())
+        .attach(AdHoc::config::<practice::PracticeLimits>())
+        .attach(queue::config())
+        .attach(AdHoc::config::<ArenaBounds>())
+        .attach(roles::bootstrap_admins())
+        .attach(compile::builder())
+        .manage(BattleStreams::default())
+        .manage(practice::PracticeRuns::default())
+        .attach(queue::workers())
+        .attach(sessions::config())
+        .attach(sessions::cleanup())
+        .register("/", rocket::catchers![auth::unauthorized, auth::forbidden])
         .mount(
             "/",
             routes![
                 users,
                 new_user,
+                user_profile,
                 new_strain,
                 strains,
                 strain,
                 delete_strain,
+                new_strain_version,
+                strain_versions,
+                strain_version,
                 login,
                 logout,
                 validate_session,
+                update_me,
+                sessions::sessions,
+                sessions::revoke_session,
+                tokens::new_api_token,
+                tokens::api_tokens,
+                tokens::revoke_api_token,
                 battle,
                 get_battle,
-                battles
+                stream::battle_stream,
+                verify::verify_battle,
+                practice::test_strain,
+                battles,
+                tournament::new_tournament,
+                tournament::tournaments,
+                tournament::tournament,
+                rating::leaderboard,
+                roles::roles,
+                roles::grant_role,
+                roles::revoke_role
             ],
         )
 }
@@ -997,3 +1559,110 @@ async fn run_migrations(rocket: rocket::Rocket<rocket::Build>) -> rocket::fairin
         None => Err(rocket),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::asynchronous::Client;
+    use rocket::serde::json::{json, Value};
+
+    /// A client of the whole server, which needs a database to run against.
+    async fn client() -> Option<Client> {
+        if std::env::var("DATABASE_URL").is_err() {
+            println!("DATABASE_URL is not set, skipping");
+            return None;
+        }
+        Some(Client::tracked(super::rocket().await).await.unwrap())
+    }
+
+    fn bearer(token: &str) -> Header<'static> {
+        Header::new("Authorization", format!("Bearer {token}"))
+    }
+
+    async fn post(
+        client: &Client,
+        path: &str,
+        token: Option<&str>,
+        body: Value,
+    ) -> (Status, Value) {
+        let mut request = client
+            .post(path.to_string())
+            .header(ContentType::JSON)
+            .body(body.to_string());
+        if let Some(token) = token {
+            request = request.header(bearer(token));
+        }
+        let response = request.dispatch().await;
+        let status = response.status();
+        (status, response.into_json().await.unwrap_or(Value::Null))
+    }
+
+    async fn update_me(client: &Client, session: &str, body: Value) -> Status {
+        client
+            .patch("/me")
+            .header(ContentType::JSON)
+            .header(bearer(session))
+            .body(body.to_string())
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[rocket::async_test]
+    async fn password_change_revokes_api_tokens() {
+        let Some(client) = client().await else {
+            return;
+        };
+        let email = format!("{}@example.com", uuid::Uuid::new_v4());
+        let credentials = json!({ "email": email, "password": "hunter22" });
+        let (status, _) = post(
+            &client,
+            "/user",
+            None,
+            json!({ "name": "tester", "email": email, "password": "hunter22" }),
+        )
+        .await;
+        assert!(status.class().is_success(), "{status}");
+        let (_, session) = post(&client, "/login", None, credentials).await;
+        let session = session["token"].as_str().unwrap().to_string();
+        let (_, token) = post(
+            &client,
+            "/me/tokens",
+            Some(&session),
+            json!({ "name": "ci", "scopes": ["strains:write"] }),
+        )
+        .await;
+        let token = token["token"].as_str().unwrap().to_string();
+        // a strain without wasm gets past the token check and is refused after it
+        let upload = json!({ "name": "nothing", "description": null });
+
+        let (status, _) = post(&client, "/strain", Some(&token), upload.clone()).await;
+        assert_eq!(status, Status::BadRequest);
+        let status = update_me(
+            &client,
+            &session,
+            json!({ "current_password": "hunter22", "name": "renamed" }),
+        )
+        .await;
+        assert_eq!(status, Status::Ok);
+        let (status, _) = post(&client, "/strain", Some(&token), upload.clone()).await;
+        assert_eq!(status, Status::BadRequest);
+
+        let status = update_me(
+            &client,
+            &session,
+            json!({ "current_password": "hunter22", "password": "hunter23" }),
+        )
+        .await;
+        assert_eq!(status, Status::Ok);
+        let (status, _) = post(&client, "/strain", Some(&token), upload).await;
+        assert_eq!(status, Status::Unauthorized);
+        let status = client
+            .get("/me")
+            .header(bearer(&session))
+            .dispatch()
+            .await
+            .status();
+        assert_eq!(status, Status::Ok);
+    }
+}
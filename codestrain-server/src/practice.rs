@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use codestrain_engine::{
+    strain_seed, Arena, GameState, Match, MatchResult, MoveProvider, Player, PluginProvider,
+    ResourceLimits, Seed, StrainError, ValidationError,
+};
+use codestrain_sdk::{BoardEncoding, MatchInfo, StrainInput, StrainOutput, Strategy, TurnContext};
+use rocket::http::Status;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use serde_with::{base64::Base64, As};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::arena::ArenaBounds;
+use crate::roles::{Authorized, UploadStrains};
+use crate::{queue, validate_strain, BattleLog, BattleViolation, NewStrainError, StrainLimits};
+
+/// The example strains, built into the server to practice against.
+const BUILT_IN: [&str; 5] = [
+    "first_choice",
+    "fill",
+    "fill-reverse",
+    "pathfinding",
+    "thefinnishvariant",
+];
+
+/// A strategy of the SDK played natively, without a plugin.
+#[derive(Default)]
+struct BuiltIn<S>(S);
+
+impl<S: Strategy> MoveProvider for BuiltIn<S> {
+    fn take_turn(&mut self, input: StrainInput) -> Result<StrainOutput, StrainError> {
+        Ok(self.0.take_turn(&TurnContext::new(input)))
+    }
+
+    fn board_encoding(&self) -> BoardEncoding {
+        BoardEncoding::Grid
+    }
+
+    fn init(&mut self, info: MatchInfo) -> Result<(), StrainError> {
+        self.0 = S::default();
+        self.0.init(&info);
+        Ok(())
+    }
+}
+
+fn built_in(name: &str) -> Option<Box<dyn MoveProvider>> {
+    Some(match name {
+        "first_choice" => Box::<BuiltIn<first_choice::FirstChoice>>::default(),
+        "fill" => Box::<BuiltIn<fill::Fill>>::default(),
+        "fill-reverse" => Box::<BuiltIn<fill_reverse::FillReverse>>::default(),
+        "pathfinding" => Box::<BuiltIn<pathfinding::Pathfinding>>::default(),
+        "thefinnishvariant" => Box::<BuiltIn<thefinnishvariant::TheFinnishVariant>>::default(),
+        _ => return None,
+    })
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct StrainTest {
+    #[serde(with = "As::<Base64>")]
+    wasm: Vec<u8>,
+    /// Names of the built-in strains to play against, all of them if left out.
+    opponents: Option<Vec<String>>,
+    #[serde(default)]
+    arena: Arena,
+    seed: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct PracticeMatch {
+    opponent: String,
+    /// Whether the tested strain was player a, and moved first.
+    first: bool,
+    /// Whether the tested strain won, `None` on a draw.
+    won: Option<bool>,
+    score: i32,
+    opponent_score: i32,
+    /// Whether the tested strain forfeited, `None` if neither did.
+    forfeit: Option<bool>,
+    log: Vec<BattleLog>,
+    violations: Vec<BattleViolation>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct PracticeResult {
+    seed: i64,
+    matches: Vec<PracticeMatch>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PracticeError {
+    #[error("Invalid arena: {0}")]
+    InvalidArena(String),
+    #[error("Unknown built-in strain {0:?}")]
+    UnknownOpponent(String),
+    #[error("Invalid strain: {0}")]
+    InvalidWasm(ValidationError),
+    #[error("A practice run of the user is already in progress")]
+    AlreadyRunning,
+    #[error("Internal error")]
+    InternalError,
+}
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for PracticeError {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        println!("{:?}", self);
+
+        match self {
+            Self::InvalidArena(message) => (Status::BadRequest, message).respond_to(req),
+            Self::UnknownOpponent(_) => (Status::BadRequest, self.to_string()).respond_to(req),
+            Self::InvalidWasm(e) => NewStrainError::InvalidWasm(e).respond_to(req),
+            Self::AlreadyRunning => (Status::TooManyRequests, self.to_string()).respond_to(req),
+            _ => Status::InternalServerError.respond_to(req),
+        }
+    }
+}
+
+/// Limits of a single practice match, configurable through the rocket config
+/// (e.g. `ROCKET_PRACTICE_MATCH_FUEL`). A run plays up to 10 matches and nobody waits for it in a
+/// queue, so they are far below the [`StrainLimits`] of a battle, which still apply per call.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct PracticeLimits {
+    practice_match_fuel: u64,
+    practice_match_budget_ms: u64,
+}
+
+impl Default for PracticeLimits {
+    fn default() -> Self {
+        Self {
+            practice_match_fuel: 200_000_000,
+            practice_match_budget_ms: 3_000,
+        }
+    }
+}
+
+impl PracticeLimits {
+    fn apply(&self, limits: ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            match_fuel: limits.match_fuel.min(self.practice_match_fuel),
+            match_budget: limits
+                .match_budget
+                .min(Duration::from_millis(self.practice_match_budget_ms)),
+            ..limits
+        }
+    }
+}
+
+/// Users with a practice run in progress, so each of them keeps at most one core busy.
+#[derive(Clone, Default)]
+pub struct PracticeRuns(Arc<Mutex<HashSet<Uuid>>>);
+
+impl PracticeRuns {
+    /// Marks a run of the user as started, unless one already is.
+    fn start(&self, user_id: Uuid) -> Option<PracticeRun> {
+        self.0.lock().unwrap().insert(user_id).then(|| PracticeRun {
+            runs: self.clone(),
+            user_id,
+        })
+    }
+}
+
+/// A practice run in progress, ended when dropped.
+struct PracticeRun {
+    runs: PracticeRuns,
+    user_id: Uuid,
+}
+
+impl Drop for PracticeRun {
+    fn drop(&mut self) {
+        self.runs.0.lock().unwrap().remove(&self.user_id);
+    }
+}
+
+/// Plays an unsaved strain against the built-in strains, once from each side.
+/// Nothing is stored, so anyone who can upload strains can try one out before publishing it,
+/// one run at a time.
+#[rocket::post("/strain/test", data = "<test>")]
+pub async fn test_strain(
+    test: Json<StrainTest>,
+    auth: Authorized<UploadStrains>,
+    practice_runs: &State<PracticeRuns>,
+    strain_limits: &State<StrainLimits>,
+    practice_limits: &State<PracticeLimits>,
+    arena_bounds: &State<ArenaBounds>,
+) -> Result<Json<PracticeResult>, PracticeError> {
+    let StrainTest {
+        wasm,
+        opponents,
+        arena,
+        seed,
+    } = test.into_inner();
+    arena_bounds
+        .check(&arena)
+        .map_err(PracticeError::InvalidArena)?;
+    let opponents = opponents.unwrap_or_else(|| BUILT_IN.map(String::from).to_vec());
+    if let Some(unknown) = opponents
+        .iter()
+        .find(|name| !BUILT_IN.contains(&name.as_str()))
+    {
+        return Err(PracticeError::UnknownOpponent(unknown.clone()));
+    }
+    let seed = seed.unwrap_or_else(queue::new_seed);
+    let limits = practice_limits.apply(ResourceLimits::from(**strain_limits));
+    let run = practice_runs
+        .start(auth.user.id.unwrap())
+        .ok_or(PracticeError::AlreadyRunning)?;
+
+    // one match after another, so a test takes at most one core
+    let matches = rocket::tokio::task::spawn_blocking(move || {
+        // held until the matches are done, even if the client goes away
+        let _run = run;
+        validate_strain(&wasm, &limits).map_err(PracticeError::InvalidWasm)?;
+        let mut matches = vec![];
+        for opponent in opponents {
+            for first in [true, false] {
+                let result = practice(arena, seed as Seed, &wasm, &opponent, first, &limits);
+                matches.push(PracticeMatch::new(opponent.clone(), first, result));
+            }
+        }
+        Ok(matches)
+    })
+    .await
+    .map_err(|_| PracticeError::InternalError)??;
+
+    Ok(Json(PracticeResult { seed, matches }))
+}
+
+/// Plays the strain against the built-in `opponent`, as player a if `first`.
+fn practice(
+    arena: Arena,
+    seed: Seed,
+    wasm: &[u8],
+    opponent: &str,
+    first: bool,
+    limits: &ResourceLimits,
+) -> MatchResult {
+    let player = if first { Player::A } else { Player::B };
+    let strain = PluginProvider::load(wasm, limits, strain_seed(seed, player));
+    let bot = built_in(opponent).expect("Opponent was checked");
+    let (a, b) = if first { (strain, bot) } else { (bot, strain) };
+    let state = GameState::with_arena(arena).expect("Arena was validated");
+    Match::new(state, a, b).run()
+}
+
+impl PracticeMatch {
+    fn new(opponent: String, first: bool, result: MatchResult) -> Self {
+        let player = if first { Player::A } else { Player::B };
+        let (score, opponent_score) = match player {
+            Player::A => (result.score_a, result.score_b),
+            Player::B => (result.score_b, result.score_a),
+        };
+        Self {
+            opponent,
+            first,
+            won: result.winner.map(|winner| winner == player),
+            score,
+            opponent_score,
+            forfeit: result.forfeit.map(|forfeit| forfeit == player),
+            log: result.log.iter().map(BattleLog::from).collect(),
+            violations: result
+                .violations
+                .iter()
+                .map(BattleViolation::from)
+                .collect(),
+        }
+    }
+}
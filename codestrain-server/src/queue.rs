@@ -0,0 +1,327 @@
+use std::time::Duration;
+
+use codestrain_engine::{Arena, ResourceLimits};
+use rocket::fairing::AdHoc;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio;
+use rocket_db_pools::{sqlx, Database};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::stream::{BattleEvent, BattleStreams};
+use crate::{get_strain_version, run_battle, BattleResult, Db, StrainLimits};
+
+/// Settings of the background battle workers, configurable through the rocket config
+/// (e.g. `ROCKET_BATTLE_WORKERS`).
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct QueueConfig {
+    battle_workers: usize,
+    battle_poll_interval_ms: u64,
+    /// How long a running job is left to its worker without hearing from it, at least a second.
+    battle_lease_secs: u64,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            battle_workers: 2,
+            battle_poll_interval_ms: 1000,
+            battle_lease_secs: 60,
+        }
+    }
+}
+
+#[derive(sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "battle_status", rename_all = "lowercase")]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum BattleStatus {
+    Pending,
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct BattleJob {
+    pub id: Uuid,
+    pub arena_id: Uuid,
+    pub arena: Json<Arena>,
+    pub version_a: Uuid,
+    pub version_b: Uuid,
+    pub status: BattleStatus,
+    pub error: Option<String>,
+    /// Missing for battles played before matches were seeded, which can't be re-run.
+    pub seed: Option<i64>,
+    pub requested_by: Option<Uuid>,
+    pub created_at: PrimitiveDateTime,
+    pub started_at: Option<PrimitiveDateTime>,
+    pub finished_at: Option<PrimitiveDateTime>,
+}
+
+/// A random seed for a new battle.
+/// Seeds are kept below 2^53 so they survive being read as a JavaScript number.
+pub fn new_seed() -> i64 {
+    (OsRng.next_u64() >> 11) as i64
+}
+
+pub async fn enqueue(
+    db: &mut PgConnection,
+    arena_id: Uuid,
+    version_a: Uuid,
+    version_b: Uuid,
+    seed: i64,
+    requested_by: Option<Uuid>,
+) -> sqlx::Result<BattleJob> {
+    let job_id = sqlx::query!(
+        r#"
+        INSERT INTO battle_jobs (arena_id, version_a, version_b, seed, requested_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        arena_id,
+        version_a,
+        version_b,
+        seed,
+        requested_by
+    )
+    .fetch_one(&mut *db)
+    .await?
+    .id;
+    get_job(db, job_id).await?.ok_or(sqlx::Error::RowNotFound)
+}
+
+pub async fn get_job(db: &mut PgConnection, job_id: Uuid) -> sqlx::Result<Option<BattleJob>> {
+    sqlx::query_as!(
+        BattleJob,
+        r#"
+        SELECT battle_jobs.id, arena_id, config AS "arena: Json<Arena>", version_a, version_b, status AS "status: BattleStatus", error, seed, requested_by, battle_jobs.created_at, started_at, finished_at
+        FROM battle_jobs
+        INNER JOIN arenas ON arenas.id = battle_jobs.arena_id
+        WHERE battle_jobs.id = $1
+        "#,
+        job_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Marks every tournament whose battles have all been played as finished.
+pub async fn finish_tournaments(db: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE tournaments
+        SET finished_at = NOW()
+        WHERE finished_at IS NULL AND NOT EXISTS (
+            SELECT 1
+            FROM tournament_battles
+            INNER JOIN battle_jobs ON battle_jobs.id = tournament_battles.battle_id
+            WHERE tournament_battles.tournament_id = tournaments.id
+                AND battle_jobs.status IN ('pending', 'running')
+        )
+        "#
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Loads the [`QueueConfig`], refusing a lease the workers couldn't renew.
+pub fn config() -> AdHoc {
+    AdHoc::try_on_ignite("Queue config", |rocket| async {
+        let config = match rocket.figment().extract::<QueueConfig>() {
+            Ok(config) => config,
+            Err(e) => {
+                rocket::error!("Invalid queue config: {}", e);
+                return Err(rocket);
+            }
+        };
+        if config.battle_lease_secs == 0 {
+            rocket::error!("Invalid queue config: battle_lease_secs must be at least 1");
+            return Err(rocket);
+        }
+        Ok(rocket.manage(config))
+    })
+}
+
+/// Starts the battle workers once rocket has launched.
+pub fn workers() -> AdHoc {
+    AdHoc::on_liftoff("Battle workers", |rocket| {
+        Box::pin(async move {
+            let pool = Db::fetch(rocket).expect("Database not attached").0.clone();
+            let config = *rocket
+                .state::<QueueConfig>()
+                .expect("Queue config not attached");
+            let limits = ResourceLimits::from(
+                *rocket
+                    .state::<StrainLimits>()
+                    .expect("Strain limits not attached"),
+            );
+            let streams = rocket
+                .state::<BattleStreams>()
+                .expect("Battle streams not managed");
+            for worker in 0..config.battle_workers {
+                tokio::spawn(work(pool.clone(), worker, config, limits, streams.clone()));
+            }
+        })
+    })
+}
+
+async fn work(
+    pool: PgPool,
+    worker: usize,
+    config: QueueConfig,
+    limits: ResourceLimits,
+    streams: BattleStreams,
+) {
+    let poll_interval = Duration::from_millis(config.battle_poll_interval_ms);
+    // a running job is handed to another worker once its worker has stopped renewing the lease,
+    // which happens when the server dies in the middle of a battle
+    let lease = Duration::from_secs(config.battle_lease_secs);
+    loop {
+        let job_id = match claim(&pool, lease).await {
+            Ok(Some(job_id)) => job_id,
+            Ok(None) => {
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+            Err(e) => {
+                println!("worker{worker}| Failed to claim a job: {e}");
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+        };
+        println!("worker{worker}| Running battle {job_id}");
+
+        // renewed for as long as the battle runs, however long the engine takes
+        let heartbeat = tokio::spawn(heartbeat(pool.clone(), job_id, lease));
+        // run in a separate task so a panicking battle only fails its own job
+        let outcome = tokio::spawn(run_job(pool.clone(), job_id, limits, streams.clone())).await;
+        heartbeat.abort();
+        let (error, event) = match outcome {
+            Ok(Ok(result)) => (None, BattleEvent::finished(&result)),
+            Ok(Err(e)) => (
+                Some(e.to_string()),
+                BattleEvent::Failed {
+                    error: e.to_string(),
+                },
+            ),
+            Err(e) => (
+                Some(e.to_string()),
+                BattleEvent::Failed {
+                    error: e.to_string(),
+                },
+            ),
+        };
+        // a played battle finished its job together with storing the result
+        if let Some(error) = error {
+            println!("worker{worker}| Battle {job_id} failed: {error}");
+            if let Err(e) = fail(&pool, job_id, error).await {
+                println!("worker{worker}| Failed to mark battle {job_id} as failed: {e}");
+            }
+        }
+        // only after the job is marked done, so late subscribers replay the stored result instead
+        streams.publish(job_id, event);
+        streams.close(job_id);
+    }
+}
+
+/// Takes the oldest pending job, or a running one whose lease has expired.
+async fn claim(pool: &PgPool, lease: Duration) -> sqlx::Result<Option<Uuid>> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE battle_jobs
+        SET status = 'running', started_at = NOW(), lease_expires_at = NOW() + make_interval(secs => $1)
+        WHERE id = (
+            SELECT id
+            FROM battle_jobs
+            WHERE status = 'pending'
+                OR (status = 'running' AND lease_expires_at < NOW())
+            ORDER BY created_at ASC
+            FOR UPDATE SKIP LOCKED
+            LIMIT 1
+        )
+        RETURNING id
+        "#,
+        lease.as_secs_f64()
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| row.id))
+}
+
+async fn run_job(
+    pool: PgPool,
+    job_id: Uuid,
+    limits: ResourceLimits,
+    streams: BattleStreams,
+) -> sqlx::Result<BattleResult> {
+    let mut db = pool.acquire().await?;
+    let job = get_job(&mut db, job_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    let strain_a = get_strain_version(&mut db, job.version_a).await?;
+    let strain_b = get_strain_version(&mut db, job.version_b).await?;
+    // back to the pool while the battle is played
+    drop(db);
+    run_battle(&pool, &job, strain_a, strain_b, &limits, &streams).await
+}
+
+/// Keeps the lease of a running job by renewing it several times per `lease`, until aborted.
+async fn heartbeat(pool: PgPool, job_id: Uuid, lease: Duration) {
+    let mut interval = tokio::time::interval(lease / 4);
+    loop {
+        interval.tick().await;
+        let result = sqlx::query!(
+            r#"
+            UPDATE battle_jobs
+            SET lease_expires_at = NOW() + make_interval(secs => $2)
+            WHERE id = $1 AND status = 'running'
+            "#,
+            job_id,
+            lease.as_secs_f64()
+        )
+        .execute(&pool)
+        .await;
+        if let Err(e) = result {
+            println!("battle{job_id}| Failed to renew the lease: {e}");
+        }
+    }
+}
+
+/// Marks the job of a battle whose result was stored as finished, in the same transaction.
+pub async fn complete(db: &mut PgConnection, job_id: Uuid) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE battle_jobs
+        SET status = 'finished', error = NULL, finished_at = NOW()
+        WHERE id = $1
+        "#,
+        job_id
+    )
+    .execute(&mut *db)
+    .await?;
+    finish_tournaments(db).await
+}
+
+async fn fail(pool: &PgPool, job_id: Uuid, error: String) -> sqlx::Result<()> {
+    let mut db = pool.acquire().await?;
+    // another worker that took over the job may have finished it meanwhile
+    sqlx::query!(
+        r#"
+        UPDATE battle_jobs
+        SET status = 'failed', error = $2, finished_at = NOW()
+        WHERE id = $1 AND status = 'running'
+        "#,
+        job_id,
+        error
+    )
+    .execute(&mut *db)
+    .await?;
+    finish_tournaments(&mut db).await
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use codestrain_engine::Player;
+use rocket::serde::{json::Json, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use futures::stream::TryStreamExt;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::{CoolerResult, Db, StrainVersion};
+
+/// Maximum rating change from a single battle.
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct RatingPoint {
+    rating: f64,
+    version_id: Option<Uuid>,
+    battle_id: Option<Uuid>,
+    created_at: PrimitiveDateTime,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct LeaderboardEntry {
+    strain_id: Uuid,
+    name: String,
+    rating: f64,
+    games: i32,
+    wins: i32,
+    draws: i32,
+    losses: i32,
+    history: Vec<RatingPoint>,
+}
+
+/// Expected score of a player rated `rating` against one rated `opponent`, between 0 and 1.
+fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Updates the Elo ratings of both strains from the outcome of a battle, once per battle.
+/// Battles of a strain against one of its own versions are not rated.
+pub async fn record_battle(
+    db: &mut sqlx::PgConnection,
+    battle_id: Uuid,
+    strain_a: &StrainVersion,
+    strain_b: &StrainVersion,
+    winner: Option<Player>,
+) -> sqlx::Result<()> {
+    if strain_a.strain_id == strain_b.strain_id {
+        return Ok(());
+    }
+    let mut tx = sqlx::Connection::begin(db).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO ratings (strain_id)
+        VALUES ($1), ($2)
+        ON CONFLICT DO NOTHING
+        "#,
+        strain_a.strain_id,
+        strain_b.strain_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let ratings = sqlx::query!(
+        r#"
+        SELECT strain_id, rating
+        FROM ratings
+        WHERE strain_id = $1 OR strain_id = $2
+        FOR UPDATE
+        "#,
+        strain_a.strain_id,
+        strain_b.strain_id
+    )
+    .fetch(&mut *tx)
+    .map_ok(|row| (row.strain_id, row.rating))
+    .try_collect::<HashMap<_, _>>()
+    .await?;
+    // checked with the ratings locked, so a retried job can't rate its battle again
+    if is_rated(&mut tx, battle_id).await? {
+        return Ok(());
+    }
+    let rating_a = ratings[&strain_a.strain_id];
+    let rating_b = ratings[&strain_b.strain_id];
+
+    let score_a = match winner {
+        Some(Player::A) => 1.0,
+        Some(Player::B) => 0.0,
+        None => 0.5,
+    };
+    let new_a = rating_a + K_FACTOR * (score_a - expected_score(rating_a, rating_b));
+    let new_b = rating_b + K_FACTOR * ((1.0 - score_a) - expected_score(rating_b, rating_a));
+
+    for (strain, rating, score) in [(strain_a, new_a, score_a), (strain_b, new_b, 1.0 - score_a)] {
+        let (wins, draws, losses) = match score {
+            s if s > 0.5 => (1, 0, 0),
+            s if s < 0.5 => (0, 0, 1),
+            _ => (0, 1, 0),
+        };
+        sqlx::query!(
+            r#"
+            UPDATE ratings
+            SET rating = $2,
+                games = games + 1,
+                wins = wins + $3,
+                draws = draws + $4,
+                losses = losses + $5,
+                updated_at = NOW()
+            WHERE strain_id = $1
+            "#,
+            strain.strain_id,
+            rating,
+            wins,
+            draws,
+            losses
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO rating_history (strain_id, version_id, battle_id, rating)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            strain.strain_id,
+            strain.id,
+            battle_id,
+            rating
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Whether the ratings were updated from the battle already.
+async fn is_rated(db: &mut sqlx::PgConnection, battle_id: Uuid) -> sqlx::Result<bool> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM rating_history WHERE battle_id = $1) AS "rated!"
+        "#,
+        battle_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row.rated)
+}
+
+#[rocket::get("/leaderboard")]
+pub async fn leaderboard(mut db: Connection<Db>) -> CoolerResult<Json<Vec<LeaderboardEntry>>> {
+    let mut history = sqlx::query!(
+        r#"
+        SELECT strain_id, version_id, battle_id, rating, created_at
+        FROM rating_history
+        ORDER BY created_at ASC
+        "#
+    )
+    .fetch(&mut **db)
+    .try_fold(
+        HashMap::<Uuid, Vec<RatingPoint>>::new(),
+        |mut acc, row| async move {
+            acc.entry(row.strain_id).or_default().push(RatingPoint {
+                rating: row.rating,
+                version_id: row.version_id,
+                battle_id: row.battle_id,
+                created_at: row.created_at,
+            });
+            Ok(acc)
+        },
+    )
+    .await?;
+
+    let entries = sqlx::query!(
+        r#"
+        SELECT ratings.strain_id, strains.name, rating, games, wins, draws, losses
+        FROM ratings
+        INNER JOIN strains ON strains.id = ratings.strain_id
+        ORDER BY rating DESC
+        "#
+    )
+    .fetch(&mut **db)
+    .map_ok(|row| LeaderboardEntry {
+        history: history.remove(&row.strain_id).unwrap_or_default(),
+        strain_id: row.strain_id,
+        name: row.name,
+        rating: row.rating,
+        games: row.games,
+        wins: row.wins,
+        draws: row.draws,
+        losses: row.losses,
+    })
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    Ok(Json(entries))
+}
@@ -0,0 +1,375 @@
+use std::marker::PhantomData;
+
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::request::{FromParam, FromRequest, Outcome, Request};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection, Database};
+
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use sqlx::PgConnection;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::{AdminUser, AuthError, Caller, Source};
+use crate::{Db, User};
+
+/// Settings of user accounts, configurable through the rocket config
+/// (e.g. `ROCKET_ADMIN_EMAILS=[admin@example.com]`, set per deployment).
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RoleConfig {
+    /// Makes the first admin: a user with one of these emails is made admin when they
+    /// register, or on startup if they already have an account, as long as there is no admin.
+    /// Later admins are granted by the existing ones.
+    admin_emails: Vec<String>,
+}
+
+impl RoleConfig {
+    pub fn is_admin_email(&self, email: &str) -> bool {
+        self.admin_emails.iter().any(|admin| admin == email)
+    }
+}
+
+#[derive(
+    sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Moderator,
+    Author,
+    Viewer,
+}
+
+impl PgHasArrayType for Role {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_user_role")
+    }
+}
+
+impl<'a> FromParam<'a> for Role {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "admin" => Ok(Self::Admin),
+            "moderator" => Ok(Self::Moderator),
+            "author" => Ok(Self::Author),
+            "viewer" => Ok(Self::Viewer),
+            _ => Err(param),
+        }
+    }
+}
+
+/// Something a user can be allowed to do.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Permission {
+    /// Create strains and new versions of their own strains.
+    UploadStrains,
+    /// Delete strains of other users.
+    ModerateStrains,
+    RunBattles,
+    ManageTournaments,
+}
+
+impl Permission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UploadStrains => "upload_strains",
+            Self::ModerateStrains => "moderate_strains",
+            Self::RunBattles => "run_battles",
+            Self::ManageTournaments => "manage_tournaments",
+        }
+    }
+}
+
+impl Role {
+    /// The new role of users that register.
+    pub const DEFAULT: Self = Self::Author;
+
+    pub fn permissions(self) -> &'static [Permission] {
+        use Permission::*;
+        match self {
+            Self::Admin => &[
+                UploadStrains,
+                ModerateStrains,
+                RunBattles,
+                ManageTournaments,
+            ],
+            Self::Moderator => &[UploadStrains, ModerateStrains, RunBattles],
+            Self::Author => &[UploadStrains],
+            Self::Viewer => &[],
+        }
+    }
+}
+
+impl User {
+    pub fn can(&self, permission: Permission) -> bool {
+        self.roles
+            .iter()
+            .any(|role| role.permissions().contains(&permission))
+    }
+}
+
+/// A permission an endpoint requires, see [`Authorized`].
+pub trait Requirement: Send + Sync + 'static {
+    const PERMISSION: Permission;
+}
+
+macro_rules! requirement {
+    ($name:ident) => {
+        pub struct $name;
+
+        impl Requirement for $name {
+            const PERMISSION: Permission = Permission::$name;
+        }
+    };
+}
+requirement!(UploadStrains);
+requirement!(RunBattles);
+requirement!(ManageTournaments);
+
+/// A user with the permission `R`, logged in or using an API token with a scope for it,
+/// see [`Caller`]. Fails with 403 without the permission or the scope.
+pub struct Authorized<R> {
+    pub user: User,
+    _requirement: PhantomData<R>,
+}
+
+#[rocket::async_trait]
+impl<'r, R: Requirement> FromRequest<'r> for Authorized<R> {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Caller { user, source } = rocket::outcome::try_outcome!(req.guard::<Caller>().await);
+        if let Source::Token { scopes, .. } = &source {
+            if !scopes
+                .iter()
+                .any(|scope| scope.permission() == R::PERMISSION)
+            {
+                return AuthError::MissingScope(R::PERMISSION).fail(req);
+            }
+        }
+        if !user.can(R::PERMISSION) {
+            return AuthError::MissingPermission(R::PERMISSION).fail(req);
+        }
+        Outcome::Success(Self {
+            user,
+            _requirement: PhantomData,
+        })
+    }
+}
+
+pub async fn grant(
+    db: &mut PgConnection,
+    user_id: Uuid,
+    role: Role,
+    granted_by: Option<Uuid>,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_roles (user_id, role, granted_by)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        role as Role,
+        granted_by
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Makes the user admin if there is no admin yet, see [`RoleConfig`].
+pub async fn bootstrap_admin(db: &mut PgConnection, user_id: Uuid) -> sqlx::Result<bool> {
+    // serializes bootstrapping with revocations, so there is never more than one first admin
+    sqlx::query!("LOCK TABLE user_roles IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *db)
+        .await?;
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO user_roles (user_id, role)
+        SELECT $1, 'admin'
+        WHERE NOT EXISTS (SELECT 1 FROM user_roles WHERE role = 'admin')
+        "#,
+        user_id
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn user_roles(db: &mut PgConnection, user_id: Uuid) -> sqlx::Result<Option<Vec<Role>>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT ARRAY(SELECT role FROM user_roles WHERE user_id = users.id ORDER BY role) AS "roles!: Vec<Role>"
+        FROM users
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|row| row.roles))
+}
+
+/// Makes the oldest user with one of the configured admin emails admin, if there is no admin.
+/// Once there is one, admins that were revoked stay revoked.
+pub fn bootstrap_admins() -> AdHoc {
+    AdHoc::try_on_ignite("Admin roles", |rocket| async {
+        let config = match rocket.figment().extract::<RoleConfig>() {
+            Ok(config) => config,
+            Err(e) => {
+                rocket::error!("Invalid role config: {}", e);
+                return Err(rocket);
+            }
+        };
+        let Some(db) = Db::fetch(&rocket) else {
+            return Err(rocket);
+        };
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_roles (user_id, role)
+            SELECT id, 'admin'
+            FROM users
+            WHERE email = ANY($1)
+                AND NOT EXISTS (SELECT 1 FROM user_roles WHERE role = 'admin')
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+            &config.admin_emails
+        )
+        .execute(&**db)
+        .await;
+        match result {
+            Ok(result) if result.rows_affected() > 0 => {
+                println!("roles| made the first admin");
+            }
+            Ok(_) => {}
+            Err(e) => {
+                rocket::error!("Failed to grant admin roles: {}", e);
+                return Err(rocket);
+            }
+        }
+        Ok(rocket.manage(config))
+    })
+}
+
+#[derive(Error, Debug)]
+pub enum RoleError {
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Unknown role")]
+    UnknownRole,
+    #[error("The last admin can't lose the admin role")]
+    LastAdmin,
+    #[error("Internal error")]
+    DbError(#[from] sqlx::Error),
+}
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for RoleError {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        println!("{:?}", self);
+
+        match self {
+            Self::UserNotFound => Status::NotFound.respond_to(req),
+            Self::UnknownRole => (Status::BadRequest, self.to_string()).respond_to(req),
+            Self::LastAdmin => (Status::Conflict, self.to_string()).respond_to(req),
+            _ => Status::InternalServerError.respond_to(req),
+        }
+    }
+}
+
+#[rocket::get("/user/<user_id_str>/roles")]
+pub async fn roles(
+    mut db: Connection<Db>,
+    user_id_str: String,
+    _admin: AdminUser,
+) -> Result<Json<Vec<Role>>, RoleError> {
+    let user_id = Uuid::parse_str(&user_id_str).map_err(|_| RoleError::UserNotFound)?;
+    let roles = user_roles(&mut db, user_id)
+        .await?
+        .ok_or(RoleError::UserNotFound)?;
+    Ok(Json(roles))
+}
+
+/// Gives a user a role, returning all roles of the user.
+#[rocket::put("/user/<user_id_str>/roles/<role>")]
+pub async fn grant_role(
+    mut db: Connection<Db>,
+    user_id_str: String,
+    role: Result<Role, &str>,
+    admin: AdminUser,
+) -> Result<Json<Vec<Role>>, RoleError> {
+    let user_id = Uuid::parse_str(&user_id_str).map_err(|_| RoleError::UserNotFound)?;
+    let role = role.map_err(|_| RoleError::UnknownRole)?;
+    user_roles(&mut db, user_id)
+        .await?
+        .ok_or(RoleError::UserNotFound)?;
+    grant(&mut db, user_id, role, admin.0.id).await?;
+    println!(
+        "roles| {} granted {role:?} to {user_id}",
+        admin.0.id.unwrap()
+    );
+    let roles = user_roles(&mut db, user_id)
+        .await?
+        .ok_or(RoleError::UserNotFound)?;
+    Ok(Json(roles))
+}
+
+/// Takes a role away from a user, returning the roles the user has left.
+#[rocket::delete("/user/<user_id_str>/roles/<role>")]
+pub async fn revoke_role(
+    mut db: Connection<Db>,
+    user_id_str: String,
+    role: Result<Role, &str>,
+    admin: AdminUser,
+) -> Result<Json<Vec<Role>>, RoleError> {
+    let user_id = Uuid::parse_str(&user_id_str).map_err(|_| RoleError::UserNotFound)?;
+    let role = role.map_err(|_| RoleError::UnknownRole)?;
+    let mut tx = sqlx::Connection::begin(&mut **db).await?;
+    // serializes revocations, so two admins can't remove each other at the same time
+    sqlx::query!("LOCK TABLE user_roles IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await?;
+    let roles = user_roles(&mut tx, user_id)
+        .await?
+        .ok_or(RoleError::UserNotFound)?;
+    if role == Role::Admin && roles.contains(&Role::Admin) {
+        let admins = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM user_roles
+            WHERE role = 'admin'
+            "#
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .count;
+        if admins <= 1 {
+            return Err(RoleError::LastAdmin);
+        }
+    }
+    sqlx::query!(
+        r#"
+        DELETE FROM user_roles
+        WHERE user_id = $1 AND role = $2
+        "#,
+        user_id,
+        role as Role
+    )
+    .execute(&mut *tx)
+    .await?;
+    let roles = user_roles(&mut tx, user_id)
+        .await?
+        .ok_or(RoleError::UserNotFound)?;
+    tx.commit().await?;
+    println!(
+        "roles| {} revoked {role:?} from {user_id}",
+        admin.0.id.unwrap()
+    );
+    Ok(Json(roles))
+}
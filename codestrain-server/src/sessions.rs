@@ -0,0 +1,243 @@
+use std::time::Duration;
+
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::tokio;
+use rocket_db_pools::{sqlx, Connection, Database};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sha2::Digest;
+use sqlx::{PgConnection, PgPool};
+use thiserror::Error;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::{CoolerResult, Db, Session};
+
+/// Settings of login sessions, configurable through the rocket config
+/// (e.g. `ROCKET_SESSION_TTL_SECS`).
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SessionConfig {
+    /// A session expires after this long without being used.
+    session_ttl_secs: u64,
+    /// How often expired sessions are removed, at least a second.
+    session_cleanup_interval_secs: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            session_ttl_secs: 24 * 60 * 60,
+            session_cleanup_interval_secs: 60 * 60,
+        }
+    }
+}
+
+/// A new random token, given to the client once.
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// What is stored of a token. The tokens are random, so a fast hash is enough.
+pub fn hash_token(token: &str) -> String {
+    sha2::Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub async fn create(
+    db: &mut PgConnection,
+    user_id: Uuid,
+    config: &SessionConfig,
+) -> sqlx::Result<Session> {
+    let token = new_token();
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO sessions (user_id, token_hash, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(secs => $3))
+        RETURNING id, created_at, expires_at
+        "#,
+        user_id,
+        hash_token(&token),
+        config.session_ttl_secs as f64
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(Session {
+        id: row.id,
+        user_id,
+        token,
+        created_at: row.created_at,
+        expires_at: row.expires_at,
+    })
+}
+
+/// Pushes the expiry of a session that was just used forward by the ttl.
+/// Written at most once a minute per session, not on every request.
+pub async fn renew(pool: &PgPool, session_id: Uuid, config: &SessionConfig) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET last_used_at = NOW(),
+            expires_at = NOW() + make_interval(secs => $2)
+        WHERE id = $1 AND last_used_at < NOW() - INTERVAL '1 minute'
+        "#,
+        session_id,
+        config.session_ttl_secs as f64
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Loads the [`SessionConfig`], refusing a cleanup interval that would never wait.
+pub fn config() -> AdHoc {
+    AdHoc::try_on_ignite("Session config", |rocket| async {
+        let config = match rocket.figment().extract::<SessionConfig>() {
+            Ok(config) => config,
+            Err(e) => {
+                rocket::error!("Invalid session config: {}", e);
+                return Err(rocket);
+            }
+        };
+        if config.session_cleanup_interval_secs == 0 {
+            rocket::error!(
+                "Invalid session config: session_cleanup_interval_secs must be at least 1"
+            );
+            return Err(rocket);
+        }
+        Ok(rocket.manage(config))
+    })
+}
+
+/// Removes expired sessions of every user, every `session_cleanup_interval_secs`.
+pub fn cleanup() -> AdHoc {
+    AdHoc::on_liftoff("Session cleanup", |rocket| {
+        Box::pin(async move {
+            let pool = Db::fetch(rocket).expect("Database not attached").0.clone();
+            let config = *rocket
+                .state::<SessionConfig>()
+                .expect("Session config not attached");
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(
+                    config.session_cleanup_interval_secs,
+                ));
+                loop {
+                    interval.tick().await;
+                    match remove_expired(&pool).await {
+                        Ok(0) => {}
+                        Ok(removed) => println!("sessions| Removed {removed} expired sessions"),
+                        Err(e) => println!("sessions| Failed to remove expired sessions: {e}"),
+                    }
+                }
+            });
+        })
+    })
+}
+
+async fn remove_expired(pool: &PgPool) -> sqlx::Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM sessions
+        WHERE expires_at < NOW()
+        "#
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// A session as listed to its user, without the token.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionInfo {
+    id: Uuid,
+    created_at: PrimitiveDateTime,
+    last_used_at: PrimitiveDateTime,
+    expires_at: PrimitiveDateTime,
+    /// Whether this is the session of the request.
+    current: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Session not found")]
+    NotFound,
+    #[error("Internal error")]
+    DbError(#[from] sqlx::Error),
+}
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for SessionError {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        println!("{:?}", self);
+
+        match self {
+            Self::NotFound => Status::NotFound,
+            _ => Status::InternalServerError,
+        }
+        .respond_to(req)
+    }
+}
+
+/// The sessions of the logged in user that haven't expired, most recently used first.
+#[rocket::get("/me/sessions")]
+pub async fn sessions(
+    mut db: Connection<Db>,
+    auth: AuthUser,
+) -> CoolerResult<Json<Vec<SessionInfo>>> {
+    let sessions = sqlx::query!(
+        r#"
+        SELECT id, created_at, last_used_at, expires_at
+        FROM sessions
+        WHERE user_id = $1 AND expires_at > NOW()
+        ORDER BY last_used_at DESC
+        "#,
+        auth.user.id
+    )
+    .fetch_all(&mut **db)
+    .await?
+    .into_iter()
+    .map(|row| SessionInfo {
+        id: row.id,
+        created_at: row.created_at,
+        last_used_at: row.last_used_at,
+        expires_at: row.expires_at,
+        current: row.id == auth.session_id,
+    })
+    .collect();
+
+    Ok(Json(sessions))
+}
+
+/// Logs out one of the sessions of the logged in user, e.g. on a lost device.
+#[rocket::delete("/me/sessions/<session_id_str>")]
+pub async fn revoke_session(
+    mut db: Connection<Db>,
+    session_id_str: String,
+    auth: AuthUser,
+) -> Result<(), SessionError> {
+    let session_id = Uuid::parse_str(&session_id_str).map_err(|_| SessionError::NotFound)?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM sessions
+        WHERE id = $1 AND user_id = $2
+        "#,
+        session_id,
+        auth.user.id
+    )
+    .execute(&mut **db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(SessionError::NotFound);
+    }
+    println!(
+        "user{}| Revoked session {session_id}",
+        auth.user.id.unwrap()
+    );
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use codestrain_engine::{GameState, MoveLog, Player};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::Serialize;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::{Shutdown, State};
+use rocket_db_pools::Connection;
+
+use uuid::Uuid;
+
+use crate::queue::{self, BattleStatus};
+use crate::{load_battle, BattleResult, CoolerResult, Db};
+
+/// Longest backlog a subscriber can fall behind before missing moves.
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", tag = "type", rename_all = "lowercase")]
+pub enum BattleEvent {
+    Move {
+        turn: i32,
+        player: bool,
+        x: i32,
+        y: i32,
+        allowed: bool,
+        score_a: i32,
+        score_b: i32,
+    },
+    Finished {
+        winner: Option<Uuid>,
+        score_a: i32,
+        score_b: i32,
+        forfeit: Option<bool>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl BattleEvent {
+    pub fn from_move(turn: usize, state: &GameState, entry: MoveLog) -> Self {
+        Self::Move {
+            turn: turn as i32,
+            player: entry.player.as_bool(),
+            x: entry.x,
+            y: entry.y,
+            allowed: true,
+            score_a: state.score(Player::A),
+            score_b: state.score(Player::B),
+        }
+    }
+
+    pub fn finished(result: &BattleResult) -> Self {
+        Self::Finished {
+            winner: result.winner,
+            score_a: result.score_a,
+            score_b: result.score_b,
+            forfeit: result.forfeit,
+        }
+    }
+
+    fn is_last(&self) -> bool {
+        !matches!(self, Self::Move { .. })
+    }
+
+    fn into_sse(self) -> Event {
+        let name = match self {
+            Self::Move { .. } => "move",
+            Self::Finished { .. } => "finished",
+            Self::Failed { .. } => "failed",
+        };
+        Event::json(&self).event(name)
+    }
+}
+
+struct LiveBattle {
+    /// Everything published so far, for subscribers joining mid-battle.
+    events: Vec<BattleEvent>,
+    sender: broadcast::Sender<BattleEvent>,
+}
+
+impl LiveBattle {
+    fn new() -> Self {
+        Self {
+            events: vec![],
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+/// Events of the battles currently being played by this server.
+#[derive(Clone, Default)]
+pub struct BattleStreams(Arc<Mutex<HashMap<Uuid, LiveBattle>>>);
+
+impl BattleStreams {
+    pub fn publish(&self, battle_id: Uuid, event: BattleEvent) {
+        let mut battles = self.0.lock().unwrap();
+        let battle = battles.entry(battle_id).or_insert_with(LiveBattle::new);
+        battle.events.push(event.clone());
+        // no receivers is fine, the event is kept in the backlog
+        let _ = battle.sender.send(event);
+    }
+
+    /// Ends the stream of a battle, after its last event has been published.
+    pub fn close(&self, battle_id: Uuid) {
+        self.0.lock().unwrap().remove(&battle_id);
+    }
+
+    fn subscribe(&self, battle_id: Uuid) -> (Vec<BattleEvent>, broadcast::Receiver<BattleEvent>) {
+        let mut battles = self.0.lock().unwrap();
+        let battle = battles.entry(battle_id).or_insert_with(LiveBattle::new);
+        (battle.events.clone(), battle.sender.subscribe())
+    }
+
+    /// Drops the stream of a battle that is not being played, unless someone else is waiting for it.
+    fn release(&self, battle_id: Uuid, receiver: broadcast::Receiver<BattleEvent>) {
+        drop(receiver);
+        let mut battles = self.0.lock().unwrap();
+        if battles
+            .get(&battle_id)
+            .is_some_and(|battle| battle.sender.receiver_count() == 0)
+        {
+            battles.remove(&battle_id);
+        }
+    }
+}
+
+/// Replays a stored battle as the events it would have streamed.
+fn replay(result: &BattleResult) -> Vec<BattleEvent> {
+    let mut board = GameState::with_arena(result.arena)
+        .map(|state| state.board().clone())
+        .unwrap_or_default();
+    let mut events = result
+        .log
+        .iter()
+        .enumerate()
+        .map(|(turn, entry)| {
+            let value = Some(entry.player);
+            board
+                .iter_mut()
+                .filter(|(pos, _)| *pos == (entry.x, entry.y))
+                .for_each(|(_, v)| *v = value);
+            let score = |player: bool| board.iter().filter(|(_, v)| *v == Some(player)).count();
+            BattleEvent::Move {
+                turn: turn as i32,
+                player: entry.player,
+                x: entry.x,
+                y: entry.y,
+                allowed: entry.allowed,
+                score_a: score(true) as i32,
+                score_b: score(false) as i32,
+            }
+        })
+        .collect::<Vec<_>>();
+    events.push(BattleEvent::finished(result));
+    events
+}
+
+/// Streams the moves of a battle as server-sent events while it is being played.
+/// Moves made before subscribing are sent first, and a finished battle is replayed from its log.
+#[rocket::get("/battle/<battle_id_str>/stream")]
+pub async fn battle_stream(
+    mut db: Connection<Db>,
+    streams: &State<BattleStreams>,
+    battle_id_str: String,
+    mut shutdown: Shutdown,
+) -> CoolerResult<Option<EventStream![]>> {
+    let Ok(battle_id) = Uuid::parse_str(&battle_id_str) else {
+        return Ok(None);
+    };
+    // subscribe before looking at the job, so a battle finishing in between is not missed
+    let (mut backlog, receiver) = streams.subscribe(battle_id);
+    let job = queue::get_job(&mut db, battle_id).await?;
+    let mut receiver = match job {
+        Some(job) if job.status == BattleStatus::Finished => {
+            streams.release(battle_id, receiver);
+            backlog = replay(&load_battle(&mut db, &job).await?);
+            None
+        }
+        Some(job) if job.status == BattleStatus::Failed => {
+            streams.release(battle_id, receiver);
+            backlog = vec![BattleEvent::Failed {
+                error: job.error.unwrap_or_default(),
+            }];
+            None
+        }
+        Some(_) => Some(receiver),
+        None => {
+            streams.release(battle_id, receiver);
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(EventStream! {
+        let mut done = false;
+        for event in backlog {
+            done = event.is_last();
+            yield event.into_sse();
+        }
+        while let Some(receiver) = receiver.as_mut().filter(|_| !done) {
+            let event = select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            done = event.is_last();
+            yield event.into_sse();
+        }
+    }))
+}
@@ -0,0 +1,262 @@
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use sqlx::PgPool;
+use thiserror::Error;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::roles::Permission;
+use crate::sessions::{hash_token, new_token};
+use crate::{CoolerResult, Db};
+
+/// Starts every API token, telling them apart from session tokens (and to secret scanners).
+const TOKEN_PREFIX: &str = "cst_";
+
+/// What an API token may be used for.
+#[derive(
+    sqlx::Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[sqlx(type_name = "api_token_scope")]
+#[serde(crate = "rocket::serde")]
+pub enum Scope {
+    /// Create strains and push new versions of them.
+    #[sqlx(rename = "strains:write")]
+    #[serde(rename = "strains:write")]
+    StrainsWrite,
+    #[sqlx(rename = "battles:run")]
+    #[serde(rename = "battles:run")]
+    BattlesRun,
+}
+
+impl PgHasArrayType for Scope {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_api_token_scope")
+    }
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::StrainsWrite => "strains:write",
+            Self::BattlesRun => "battles:run",
+        }
+    }
+
+    /// The permission the scope grants, if the user of the token has it.
+    pub fn permission(self) -> Permission {
+        match self {
+            Self::StrainsWrite => Permission::UploadStrains,
+            Self::BattlesRun => Permission::RunBattles,
+        }
+    }
+}
+
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+pub struct TokenOwner {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<Scope>,
+}
+
+/// Finds the API token and records that it was used, at most once a minute.
+pub async fn lookup(pool: &PgPool, token: &str) -> sqlx::Result<Option<TokenOwner>> {
+    let token = sqlx::query_as!(
+        TokenOwner,
+        r#"
+        SELECT id, user_id, scopes AS "scopes!: Vec<Scope>"
+        FROM api_tokens
+        WHERE token_hash = $1
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(pool)
+    .await?;
+    if let Some(token) = &token {
+        sqlx::query!(
+            r#"
+            UPDATE api_tokens
+            SET last_used_at = NOW()
+            WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            "#,
+            token.id
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(token)
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiToken {
+    id: Uuid,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: PrimitiveDateTime,
+    last_used_at: Option<PrimitiveDateTime>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct NewApiToken {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+/// A created token, the only time the token itself is given out.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct CreatedApiToken {
+    token: String,
+    #[serde(flatten)]
+    info: ApiToken,
+}
+
+#[derive(Error, Debug)]
+pub enum TokenError {
+    #[error("Token name can't be empty")]
+    InvalidName,
+    #[error("At least one scope is required")]
+    NoScopes,
+    #[error("Scope {} needs a permission the user doesn't have", .0.as_str())]
+    ScopeNotAllowed(Scope),
+    #[error("A token with this name already exists")]
+    NameTaken,
+    #[error("Token not found")]
+    NotFound,
+    #[error("Internal error")]
+    DbError(#[from] sqlx::Error),
+}
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for TokenError {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        println!("{:?}", self);
+
+        match self {
+            Self::InvalidName | Self::NoScopes => {
+                (Status::BadRequest, self.to_string()).respond_to(req)
+            }
+            Self::ScopeNotAllowed(_) => (Status::Forbidden, self.to_string()).respond_to(req),
+            Self::NameTaken => (Status::Conflict, self.to_string()).respond_to(req),
+            Self::NotFound => Status::NotFound.respond_to(req),
+            _ => Status::InternalServerError.respond_to(req),
+        }
+    }
+}
+
+/// Creates a personal API token, e.g. for uploading strains from CI.
+/// It can be used as the bearer token wherever one of its scopes is needed.
+#[rocket::post("/me/tokens", data = "<request>")]
+pub async fn new_api_token(
+    mut db: Connection<Db>,
+    request: Json<NewApiToken>,
+    auth: AuthUser,
+) -> Result<Created<Json<CreatedApiToken>>, TokenError> {
+    let NewApiToken { name, mut scopes } = request.into_inner();
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(TokenError::InvalidName);
+    }
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(TokenError::NoScopes);
+    }
+    if let Some(&scope) = scopes
+        .iter()
+        .find(|scope| !auth.user.can(scope.permission()))
+    {
+        return Err(TokenError::ScopeNotAllowed(scope));
+    }
+
+    let token = format!("{TOKEN_PREFIX}{}", new_token());
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (user_id, name, token_hash, scopes)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, created_at
+        "#,
+        auth.user.id,
+        name,
+        hash_token(&token),
+        &scopes as &[Scope]
+    )
+    .fetch_one(&mut **db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.constraint().is_some() => TokenError::NameTaken,
+        e => TokenError::DbError(e),
+    })?;
+    println!(
+        "user{}| Created API token {} with scopes {scopes:?}",
+        auth.user.id.unwrap(),
+        row.id
+    );
+
+    let created = CreatedApiToken {
+        token,
+        info: ApiToken {
+            id: row.id,
+            name,
+            scopes,
+            created_at: row.created_at,
+            last_used_at: None,
+        },
+    };
+    Ok(Created::new(format!("/me/tokens/{}", row.id)).body(Json(created)))
+}
+
+#[rocket::get("/me/tokens")]
+pub async fn api_tokens(
+    mut db: Connection<Db>,
+    auth: AuthUser,
+) -> CoolerResult<Json<Vec<ApiToken>>> {
+    let tokens = sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT id, name, scopes AS "scopes!: Vec<Scope>", created_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        auth.user.id
+    )
+    .fetch_all(&mut **db)
+    .await?;
+
+    Ok(Json(tokens))
+}
+
+#[rocket::delete("/me/tokens/<token_id_str>")]
+pub async fn revoke_api_token(
+    mut db: Connection<Db>,
+    token_id_str: String,
+    auth: AuthUser,
+) -> Result<(), TokenError> {
+    let token_id = Uuid::parse_str(&token_id_str).map_err(|_| TokenError::NotFound)?;
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM api_tokens
+        WHERE id = $1 AND user_id = $2
+        "#,
+        token_id,
+        auth.user.id
+    )
+    .execute(&mut **db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(TokenError::NotFound);
+    }
+    println!(
+        "user{}| Revoked API token {token_id}",
+        auth.user.id.unwrap()
+    );
+    Ok(())
+}
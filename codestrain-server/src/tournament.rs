@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use codestrain_engine::Arena;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::{json::Json, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use futures::stream::TryStreamExt;
+use thiserror::Error;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::roles::{Authorized, ManageTournaments};
+use crate::{arena, queue, Db};
+
+#[derive(Error, Debug)]
+pub enum TournamentError {
+    #[error("Tournament not found")]
+    NotFound,
+    #[error("Internal error")]
+    DbError(#[from] sqlx::Error),
+}
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for TournamentError {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        println!("{:?}", self);
+
+        match self {
+            Self::NotFound => Status::NotFound,
+            _ => Status::InternalServerError,
+        }
+        .respond_to(req)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct Tournament {
+    id: Uuid,
+    arena_id: Uuid,
+    arena: sqlx::types::Json<Arena>,
+    created_by: Option<Uuid>,
+    created_at: PrimitiveDateTime,
+    finished_at: Option<PrimitiveDateTime>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct Standing {
+    strain_id: Uuid,
+    version_id: Uuid,
+    name: String,
+    played: i32,
+    wins: i32,
+    draws: i32,
+    losses: i32,
+    points: i32,
+    score_for: i32,
+    score_against: i32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct TournamentStandings {
+    #[serde(flatten)]
+    tournament: Tournament,
+    standings: Vec<Standing>,
+    battles: Vec<Uuid>,
+}
+
+const POINTS_WIN: i32 = 3;
+const POINTS_DRAW: i32 = 1;
+
+/// Schedules every strain against every other strain, in both seatings.
+/// Pairings that already have a result for the same versions, or are still queued, are reused
+/// instead of playing again. The tournament is finished once all its battles have been played.
+#[rocket::post("/tournament")]
+pub async fn new_tournament(
+    mut db: Connection<Db>,
+    auth: Authorized<ManageTournaments>,
+) -> Result<Created<Json<TournamentStandings>>, TournamentError> {
+    let user = auth.user;
+
+    // workers must not see the tournament before all of its battles are linked
+    let mut tx = sqlx::Connection::begin(&mut **db).await?;
+    let arena_id = arena::arena_id(&mut tx, &Arena::default()).await?;
+    let tournament_id = sqlx::query!(
+        r#"
+        INSERT INTO tournaments (arena_id, created_by)
+        VALUES ($1, $2)
+        RETURNING id
+        "#,
+        arena_id,
+        user.id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+    println!(
+        "tournament{tournament_id}| Created by user {}",
+        user.id.unwrap()
+    );
+
+    // enter the latest version of every strain
+    let entries = sqlx::query!(
+        r#"
+        INSERT INTO tournament_entries (tournament_id, strain_id, version_id)
+        SELECT DISTINCT ON (strain_id) $1::uuid, strain_id, id
+        FROM strain_versions
+        ORDER BY strain_id, version DESC
+        RETURNING version_id
+        "#,
+        tournament_id
+    )
+    .fetch(&mut *tx)
+    .map_ok(|row| row.version_id)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    let mut queued = 0;
+    for &version_a in &entries {
+        for &version_b in &entries {
+            if version_a == version_b {
+                continue;
+            }
+            let existing = sqlx::query!(
+                r#"
+                SELECT id
+                FROM battle_jobs
+                WHERE version_a = $1 AND version_b = $2 AND arena_id = $3 AND status <> 'failed'
+                ORDER BY created_at DESC
+                LIMIT 1
+                "#,
+                version_a,
+                version_b,
+                arena_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            let battle_id = match existing {
+                Some(row) => row.id,
+                None => {
+                    queued += 1;
+                    let seed = queue::new_seed();
+                    queue::enqueue(&mut tx, arena_id, version_a, version_b, seed, user.id)
+                        .await?
+                        .id
+                }
+            };
+            sqlx::query!(
+                r#"
+                INSERT INTO tournament_battles (tournament_id, battle_id)
+                VALUES ($1, $2)
+                "#,
+                tournament_id,
+                battle_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    println!(
+        "tournament{tournament_id}| {} strains entered, {queued} battles queued",
+        entries.len()
+    );
+    // nothing left to play if every pairing had a result already
+    queue::finish_tournaments(&mut db).await?;
+
+    let standings = get_standings(&mut db, tournament_id).await?;
+    Ok(Created::new(format!("/tournament/{tournament_id}")).body(Json(standings)))
+}
+
+#[rocket::get("/tournament")]
+pub async fn tournaments(mut db: Connection<Db>) -> Result<Json<Vec<Tournament>>, TournamentError> {
+    let tournaments = sqlx::query_as!(
+        Tournament,
+        r#"
+        SELECT tournaments.id, arena_id, config AS "arena: sqlx::types::Json<Arena>", created_by, tournaments.created_at, finished_at
+        FROM tournaments
+        INNER JOIN arenas ON arenas.id = tournaments.arena_id
+        ORDER BY tournaments.created_at DESC
+        "#
+    )
+    .fetch(&mut **db)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    Ok(Json(tournaments))
+}
+
+#[rocket::get("/tournament/<tournament_id_str>")]
+pub async fn tournament(
+    mut db: Connection<Db>,
+    tournament_id_str: String,
+) -> Result<Json<TournamentStandings>, TournamentError> {
+    let tournament_id =
+        Uuid::parse_str(&tournament_id_str).map_err(|_| TournamentError::NotFound)?;
+    Ok(Json(get_standings(&mut db, tournament_id).await?))
+}
+
+async fn get_standings(
+    db: &mut Connection<Db>,
+    tournament_id: Uuid,
+) -> Result<TournamentStandings, TournamentError> {
+    let tournament = sqlx::query_as!(
+        Tournament,
+        r#"
+        SELECT tournaments.id, arena_id, config AS "arena: sqlx::types::Json<Arena>", created_by, tournaments.created_at, finished_at
+        FROM tournaments
+        INNER JOIN arenas ON arenas.id = tournaments.arena_id
+        WHERE tournaments.id = $1
+        "#,
+        tournament_id
+    )
+    .fetch_optional(&mut ***db)
+    .await?
+    .ok_or(TournamentError::NotFound)?;
+
+    let mut standings = sqlx::query!(
+        r#"
+        SELECT tournament_entries.strain_id, tournament_entries.version_id, strains.name
+        FROM tournament_entries
+        INNER JOIN strains ON strains.id = tournament_entries.strain_id
+        WHERE tournament_id = $1
+        "#,
+        tournament_id
+    )
+    .fetch(&mut ***db)
+    .map_ok(|row| {
+        (
+            row.strain_id,
+            Standing {
+                strain_id: row.strain_id,
+                version_id: row.version_id,
+                name: row.name,
+                played: 0,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                points: 0,
+                score_for: 0,
+                score_against: 0,
+            },
+        )
+    })
+    .try_collect::<HashMap<_, _>>()
+    .await?;
+
+    let battles = sqlx::query!(
+        r#"
+        SELECT battles.id, strain_a, strain_b, winner, score_a, score_b
+        FROM tournament_battles
+        INNER JOIN battles ON battles.id = tournament_battles.battle_id
+        WHERE tournament_id = $1
+        ORDER BY battles.created_at ASC
+        "#,
+        tournament_id
+    )
+    .fetch(&mut ***db)
+    .try_collect::<Vec<_>>()
+    .await?;
+    for battle in &battles {
+        for (strain, score_for, score_against) in [
+            (battle.strain_a, battle.score_a, battle.score_b),
+            (battle.strain_b, battle.score_b, battle.score_a),
+        ] {
+            let Some(standing) = standings.get_mut(&strain) else {
+                continue;
+            };
+            standing.played += 1;
+            standing.score_for += score_for;
+            standing.score_against += score_against;
+            match battle.winner {
+                Some(winner) if winner == strain => {
+                    standing.wins += 1;
+                    standing.points += POINTS_WIN;
+                }
+                Some(_) => standing.losses += 1,
+                None => {
+                    standing.draws += 1;
+                    standing.points += POINTS_DRAW;
+                }
+            }
+        }
+    }
+
+    let mut standings = standings.into_values().collect::<Vec<_>>();
+    standings.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then((b.score_for - b.score_against).cmp(&(a.score_for - a.score_against)))
+            .then(a.name.cmp(&b.name))
+    });
+
+    Ok(TournamentStandings {
+        tournament,
+        standings,
+        battles: battles.iter().map(|battle| battle.id).collect(),
+    })
+}
@@ -0,0 +1,127 @@
+use codestrain_engine::{Player, ResourceLimits, Seed};
+use rocket::serde::{json::Json, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+
+use uuid::Uuid;
+
+use crate::queue::{self, BattleStatus};
+use crate::roles::{Authorized, RunBattles};
+use crate::{get_strain_version, load_battle, play, BattleError, Db, StrainLimits};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct Outcome {
+    winner: Option<Uuid>,
+    score_a: i32,
+    score_b: i32,
+    forfeit: Option<bool>,
+    moves: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct BattleVerification {
+    id: Uuid,
+    seed: i64,
+    /// Whether the replay made the same moves with the same outcome.
+    reproduced: bool,
+    /// Index of the first move that was different in the replay, if any.
+    first_difference: Option<usize>,
+    stored: Outcome,
+    replayed: Outcome,
+}
+
+/// Plays a finished battle again with its seed and checks that it plays out the same way.
+/// Nothing is stored, the original result stays as it is. Takes as long as a battle, so it needs the
+/// same permission as running one.
+#[rocket::post("/battle/<battle_id_str>/verify")]
+pub async fn verify_battle(
+    mut db: Connection<Db>,
+    battle_id_str: String,
+    auth: Authorized<RunBattles>,
+    strain_limits: &State<StrainLimits>,
+) -> Result<Json<BattleVerification>, BattleError> {
+    let user = auth.user;
+    let battle_id = Uuid::parse_str(&battle_id_str).map_err(|_| BattleError::NotFound)?;
+    let job = queue::get_job(&mut db, battle_id)
+        .await?
+        .ok_or(BattleError::NotFound)?;
+    if job.status != BattleStatus::Finished {
+        return Err(BattleError::NotFinished);
+    }
+    let seed = job.seed.ok_or(BattleError::NoSeed)?;
+    let battle = load_battle(&mut db, &job).await?;
+    let wasm_a = get_strain_version(&mut db, job.version_a).await?.wasm;
+    let wasm_b = get_strain_version(&mut db, job.version_b).await?.wasm;
+    println!(
+        "battle{battle_id}| user {} is verifying the battle",
+        user.id.unwrap()
+    );
+
+    let limits = ResourceLimits::from(**strain_limits);
+    let arena = job.arena.0;
+    let result = rocket::tokio::task::spawn_blocking(move || {
+        play(
+            arena,
+            seed as Seed,
+            wasm_a.expect("No wasm for strain a"),
+            wasm_b.expect("No wasm for strain b"),
+            &limits,
+            |_, _| {},
+        )
+    })
+    .await
+    .expect("Battle panicked");
+
+    let stored_moves = battle
+        .log
+        .iter()
+        .map(|entry| (entry.player, entry.x, entry.y));
+    let replayed_moves = result
+        .log
+        .iter()
+        .map(|entry| (entry.player.as_bool(), entry.x, entry.y));
+    let first_difference = stored_moves
+        .zip(replayed_moves)
+        .position(|(stored, replayed)| stored != replayed)
+        .or_else(|| {
+            // one log is a prefix of the other
+            (battle.log.len() != result.log.len()).then(|| battle.log.len().min(result.log.len()))
+        });
+    let stored = Outcome {
+        winner: battle.winner,
+        score_a: battle.score_a,
+        score_b: battle.score_b,
+        forfeit: battle.forfeit,
+        moves: battle.log.len(),
+    };
+    let replayed = Outcome {
+        winner: result.winner.map(|winner| match winner {
+            Player::A => battle.strain_a,
+            Player::B => battle.strain_b,
+        }),
+        score_a: result.score_a,
+        score_b: result.score_b,
+        forfeit: result.forfeit.map(Player::as_bool),
+        moves: result.log.len(),
+    };
+    let reproduced = first_difference.is_none() && stored == replayed;
+    println!(
+        "battle{battle_id}| Replay {}",
+        if reproduced {
+            "matches"
+        } else {
+            "does not match"
+        }
+    );
+
+    Ok(Json(BattleVerification {
+        id: battle_id,
+        seed,
+        reproduced,
+        first_difference,
+        stored,
+        replayed,
+    }))
+}
@@ -1,14 +1,16 @@
-use codestrain_common::*;
-use extism_pdk::*;
+use codestrain_sdk::*;
 
-#[plugin_fn]
-pub fn take_turn(Json(input): Json<StrainInput>) -> FnResult<Json<StrainOutput>> {
-    // find the option with the max euclidean distance
-    let farthest = input
-        .allowed
-        .iter()
-        .max_by_key(|(x, y)| (x.pow(2) + y.pow(2)))
-        .unwrap();
+#[derive(Default)]
+pub struct FillReverse;
 
-    Ok(Json(*farthest))
+impl Strategy for FillReverse {
+    fn take_turn(&mut self, ctx: &TurnContext) -> Move {
+        // find the option with the max euclidean distance
+        *ctx.allowed()
+            .iter()
+            .max_by_key(|(x, y)| x.pow(2) + y.pow(2))
+            .unwrap()
+    }
 }
+
+export_strain!(FillReverse);
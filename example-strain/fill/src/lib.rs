@@ -1,14 +1,16 @@
-use codestrain_common::*;
-use extism_pdk::*;
+use codestrain_sdk::*;
 
-#[plugin_fn]
-pub fn take_turn(Json(input): Json<StrainInput>) -> FnResult<Json<StrainOutput>> {
-    // find the option with the min euclidean distance
-    let closest = input
-        .allowed
-        .iter()
-        .min_by_key(|(x, y)| (x.pow(2) + y.pow(2)))
-        .unwrap();
+#[derive(Default)]
+pub struct Fill;
 
-    Ok(Json(*closest))
+impl Strategy for Fill {
+    fn take_turn(&mut self, ctx: &TurnContext) -> Move {
+        // find the option with the min euclidean distance
+        *ctx.allowed()
+            .iter()
+            .min_by_key(|(x, y)| x.pow(2) + y.pow(2))
+            .unwrap()
+    }
 }
+
+export_strain!(Fill);
@@ -1,9 +1,12 @@
-use codestrain_common::*;
-use extism_pdk::*;
+use codestrain_sdk::*;
 
-#[plugin_fn]
-pub fn take_turn(Json(input): Json<StrainInput>) -> FnResult<Json<StrainOutput>> {
-    let allowed = input.allowed;
-    let first = allowed.first().expect("No allowed moves!");
-    Ok(Json(*first))
+#[derive(Default)]
+pub struct FirstChoice;
+
+impl Strategy for FirstChoice {
+    fn take_turn(&mut self, ctx: &TurnContext) -> Move {
+        ctx.allowed()[0]
+    }
 }
+
+export_strain!(FirstChoice);
@@ -1,11 +1,12 @@
-use std::collections::HashSet;
+use codestrain_sdk::*;
 
-use codestrain_common::*;
-use extism_pdk::*;
+#[derive(Default)]
+pub struct Random;
 
-#[plugin_fn]
-pub fn take_turn(Json(input): Json<StrainInput>) -> FnResult<Json<StrainOutput>> {
-    let allowed: HashSet<(i32, i32)> = HashSet::from_iter(input.allowed);
-    let first = allowed.iter().next().expect("No allowed moves!");
-    Ok(Json(*first))
+impl Strategy for Random {
+    fn take_turn(&mut self, ctx: &TurnContext) -> Move {
+        ctx.allowed()[random_below(ctx.allowed().len())]
+    }
 }
+
+export_strain!(Random);